@@ -1,19 +1,30 @@
+use itertools::Itertools;
 use std::collections::HashSet;
 use std::fs::read_to_string;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 enum Instruction {
     Acc(i64),
     Jmp(i64),
     Nop(i64),
 }
 
+impl Instruction {
+    fn flipped(&self) -> Option<Instruction> {
+        match self {
+            Instruction::Jmp(offset) => Some(Instruction::Nop(*offset)),
+            Instruction::Nop(offset) => Some(Instruction::Jmp(*offset)),
+            Instruction::Acc(_) => None,
+        }
+    }
+}
+
 fn parse_offset(offset: &str) -> i64 {
     let unsigned: i64 = offset[1..].parse().unwrap();
-    if offset.chars().nth(0).unwrap() == '+' {
+    if offset.starts_with('+') {
         unsigned
     } else {
-        unsigned * -1
+        -unsigned
     }
 }
 
@@ -30,39 +41,134 @@ fn parse_instruction(line: &str) -> Instruction {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum ExecuteResult {
     Terminate(i64),
     InfiniteLoop(i64),
+    FuelExhausted(i64),
+    // A jump left the program somewhere other than right after the last line.
+    OutOfBounds(i64),
+}
+
+struct Machine<'a> {
+    instructions: &'a [Instruction],
+    instruction_pointer: usize,
+    accumulator: i64,
+}
+
+impl<'a> Machine<'a> {
+    fn new(instructions: &'a [Instruction]) -> Self {
+        Machine {
+            instructions,
+            instruction_pointer: 0,
+            accumulator: 0,
+        }
+    }
+
+    fn terminated(&self) -> bool {
+        self.instruction_pointer == self.instructions.len()
+    }
+
+    // Returns false, without moving, if a jump would leave `0..=instructions.len()`.
+    fn step(&mut self) -> bool {
+        match &self.instructions[self.instruction_pointer] {
+            Instruction::Jmp(offset) => {
+                match isize::try_from(*offset)
+                    .ok()
+                    .and_then(|offset| self.instruction_pointer.checked_add_signed(offset))
+                    .filter(|target| *target <= self.instructions.len())
+                {
+                    Some(target) => self.instruction_pointer = target,
+                    None => return false,
+                }
+            }
+            Instruction::Acc(offset) => {
+                self.accumulator += offset;
+                self.instruction_pointer += 1
+            }
+            Instruction::Nop(_) => self.instruction_pointer += 1,
+        }
+        true
+    }
 }
 
 fn execute(instructions: &[Instruction]) -> ExecuteResult {
     let mut visited_lines = HashSet::new();
-    let mut instruction_pointer: usize = 0;
-    let mut accumulator: i64 = 0;
+    let mut machine = Machine::new(instructions);
 
     loop {
-        if instruction_pointer == instructions.len() {
-            return ExecuteResult::Terminate(accumulator);
+        if machine.terminated() {
+            return ExecuteResult::Terminate(machine.accumulator);
         }
-        let next_instruction = &instructions[instruction_pointer];
 
-        let not_already_visited = visited_lines.insert(instruction_pointer);
+        let not_already_visited = visited_lines.insert(machine.instruction_pointer);
         if !not_already_visited {
-            return ExecuteResult::InfiniteLoop(accumulator);
+            return ExecuteResult::InfiniteLoop(machine.accumulator);
         }
 
-        match next_instruction {
-            Instruction::Jmp(offset) => {
-                instruction_pointer = (instruction_pointer as i64 + offset) as usize
+        if !machine.step() {
+            return ExecuteResult::OutOfBounds(machine.accumulator);
+        }
+    }
+}
+
+// Runs at most `fuel` instructions. Unlike `execute` this doesn't rely on the
+// instruction pointer being the only state, so it keeps working once the VM
+// grows registers or conditional branches.
+fn execute_with_fuel(instructions: &[Instruction], fuel: usize) -> ExecuteResult {
+    let mut machine = Machine::new(instructions);
+
+    for _ in 0..fuel {
+        if machine.terminated() {
+            return ExecuteResult::Terminate(machine.accumulator);
+        }
+        if !machine.step() {
+            return ExecuteResult::OutOfBounds(machine.accumulator);
+        }
+    }
+
+    if machine.terminated() {
+        ExecuteResult::Terminate(machine.accumulator)
+    } else {
+        ExecuteResult::FuelExhausted(machine.accumulator)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Repair {
+    patched_lines: Vec<usize>,
+    accumulator: i64,
+}
+
+// Flips `jmp` <-> `nop` on up to `max_patches` lines and returns every
+// smallest set of flipped lines which makes the program terminate within `fuel`
+// steps. Patches which make a jump leave the program are skipped.
+fn find_repairs(instructions: &[Instruction], max_patches: usize, fuel: usize) -> Vec<Repair> {
+    let patchable_lines: Vec<usize> = instructions
+        .iter()
+        .positions(|i| i.flipped().is_some())
+        .collect();
+
+    for k in 1..=max_patches {
+        let mut repairs = vec![];
+        for lines in patchable_lines.iter().copied().combinations(k) {
+            let mut patched = instructions.to_vec();
+            for &line in &lines {
+                patched[line] = instructions[line].flipped().unwrap();
             }
-            Instruction::Acc(offset) => {
-                accumulator += offset;
-                instruction_pointer += 1
+            if let ExecuteResult::Terminate(accumulator) = execute_with_fuel(&patched, fuel) {
+                repairs.push(Repair {
+                    patched_lines: lines,
+                    accumulator,
+                });
             }
-            Instruction::Nop(_) => instruction_pointer += 1,
+        }
+        if !repairs.is_empty() {
+            return repairs;
         }
     }
+
+    vec![]
 }
 
 fn main() {
@@ -72,36 +178,105 @@ fn main() {
 
     // Task 1
     // Result: 1394
-    let result = execute(&instructions);
-    println!("{:?}", result);
+    if let ExecuteResult::InfiniteLoop(acc) = execute(&instructions) {
+        println!("{}", acc);
+    }
 
-    let mut instructions = instructions;
     // Task 2
-    for i in 0..instructions.len() {
-        let mut revert_instruction = None;
-        match instructions[i] {
-            Instruction::Nop(offset) => {
-                std::mem::replace(&mut instructions[i], Instruction::Jmp(offset));
-                revert_instruction = Some(Instruction::Nop(offset));
-            }
-            Instruction::Jmp(offset) => {
-                std::mem::replace(&mut instructions[i], Instruction::Nop(offset));
-                revert_instruction = Some(Instruction::Jmp(offset));
-            }
-            Instruction::Acc(_) => continue,
-        };
-        if let ExecuteResult::Terminate(acc) = execute(&instructions) {
-            println!("found termination!");
-            // Result: 1626
-            println!("{}", acc);
-            break;
-        }
+    // Without registers a terminating run visits every line at most once.
+    let fuel = instructions.len() + 1;
+    for repair in find_repairs(&instructions, 1, fuel) {
+        // Result: 1626
+        println!("{}", repair.accumulator);
+    }
+}
 
-        match revert_instruction {
-            Some(instruction) => {
-                std::mem::replace(&mut instructions[i], instruction);
-            }
-            _ => {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    fn example() -> Vec<Instruction> {
+        EXAMPLE.lines().map(parse_instruction).collect()
+    }
+
+    #[test]
+    fn test_execute_with_fuel() {
+        let instructions = example();
+        assert_eq!(execute(&instructions), ExecuteResult::InfiniteLoop(5));
+        assert_eq!(
+            execute_with_fuel(&instructions, 7),
+            ExecuteResult::FuelExhausted(5)
+        );
+        assert_eq!(
+            execute_with_fuel(&instructions, 3),
+            ExecuteResult::FuelExhausted(1)
+        );
+    }
+
+    #[test]
+    fn test_find_repairs() {
+        let instructions = example();
+        assert_eq!(
+            find_repairs(&instructions, 2, instructions.len() + 1),
+            vec![Repair {
+                patched_lines: vec![7],
+                accumulator: 8
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_repairs_needing_two_patches() {
+        let instructions: Vec<Instruction> = "jmp +0\nnop +0\njmp -1"
+            .lines()
+            .map(parse_instruction)
+            .collect();
+        assert_eq!(
+            find_repairs(&instructions, 1, instructions.len() + 1),
+            vec![]
+        );
+        assert_eq!(
+            find_repairs(&instructions, 3, instructions.len() + 1),
+            vec![Repair {
+                patched_lines: vec![0, 2],
+                accumulator: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_jumps_out_of_bounds() {
+        for (program, flipped_result) in [
+            ("nop +5\njmp +0", ExecuteResult::OutOfBounds(0)),
+            ("nop -5\njmp +0", ExecuteResult::OutOfBounds(0)),
+        ] {
+            let instructions: Vec<Instruction> = program.lines().map(parse_instruction).collect();
+            let mut flipped = instructions.clone();
+            flipped[0] = instructions[0].flipped().unwrap();
+            assert_eq!(execute(&flipped), flipped_result);
+            assert_eq!(execute_with_fuel(&flipped, 10), flipped_result);
+
+            assert_eq!(
+                find_repairs(&instructions, 2, instructions.len() + 1),
+                vec![Repair {
+                    patched_lines: vec![1],
+                    accumulator: 0
+                }]
+            );
         }
+
+        let instructions: Vec<Instruction> =
+            "acc +2\njmp +2".lines().map(parse_instruction).collect();
+        assert_eq!(execute(&instructions), ExecuteResult::OutOfBounds(2));
     }
 }
@@ -1,69 +1,270 @@
-use std::fs::read_to_string;
-
-fn is_sum_of_numbers(needle: u64, haystack: &Vec<u64>) -> bool {
-    for number in haystack {
-        if *number <= needle {
-            let x = needle - number;
-            if haystack.contains(&x) && x != needle {
-                return true;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::io::{self, BufRead, BufReader};
+
+#[derive(Debug, PartialEq)]
+struct InvalidNumber {
+    position: usize,
+    value: u64,
+}
+
+// Validates numbers one at a time, only remembering the last `preamble` numbers. The sums of all
+// pairs of different numbers in the window are kept in a multiset, so each push updates
+// O(preamble) sums and the pair check itself is a single lookup.
+struct XmasValidator {
+    preamble: usize,
+    window: VecDeque<u64>,
+    pair_sums: HashMap<u64, usize>,
+    position: usize,
+}
+
+impl XmasValidator {
+    fn new(preamble: usize) -> Self {
+        XmasValidator {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            pair_sums: HashMap::new(),
+            position: 0,
+        }
+    }
+
+    fn is_sum_of_pair(&self, needle: u64) -> bool {
+        self.pair_sums.contains_key(&needle)
+    }
+
+    // Sums which don't fit into a u64 can't match any number and are left out.
+    fn pair_sums_with(&self, number: u64) -> impl Iterator<Item = u64> + '_ {
+        self.window
+            .iter()
+            .filter(move |other| **other != number)
+            .filter_map(move |other| other.checked_add(number))
+    }
+
+    fn push(&mut self, number: u64) -> Option<InvalidNumber> {
+        let invalid = if self.window.len() == self.preamble && !self.is_sum_of_pair(number) {
+            Some(InvalidNumber {
+                position: self.position,
+                value: number,
+            })
+        } else {
+            None
+        };
+
+        if self.window.len() == self.preamble {
+            if let Some(oldest) = self.window.pop_front() {
+                for sum in self.pair_sums_with(oldest).collect::<Vec<_>>() {
+                    let count = self.pair_sums.get_mut(&sum).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        self.pair_sums.remove(&sum);
+                    }
+                }
+            }
+        }
+        for sum in self.pair_sums_with(number).collect::<Vec<_>>() {
+            *self.pair_sums.entry(sum).or_insert(0) += 1;
+        }
+        self.window.push_back(number);
+        self.position += 1;
+
+        invalid
+    }
+}
+
+#[derive(Debug)]
+enum StreamError {
+    Io(io::Error),
+    // `line` counts from 1, including blank lines.
+    Parse { line: usize, text: String },
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Io(e) => write!(f, "can't read numbers: {}", e),
+            StreamError::Parse { line, text } => {
+                write!(f, "line {}: not a number: {:?}", line, text)
             }
         }
     }
-    false
 }
 
-fn main() {
-    let content = read_to_string("./inputs/day09.txt").expect("file not found");
-    let numbers: Vec<u64> = content
+// Blank lines are skipped. Reading goes on after an error, so a caller may choose to only report
+// malformed lines.
+fn invalid_numbers_in<R: BufRead>(
+    reader: R,
+    preamble: usize,
+) -> impl Iterator<Item = Result<InvalidNumber, StreamError>> {
+    let mut validator = XmasValidator::new(preamble);
+    reader
         .lines()
-        .map(str::parse)
-        .map(Result::unwrap)
-        .collect();
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(text) if text.trim().is_empty()))
+        .filter_map(move |(index, line)| {
+            let text = match line {
+                Ok(text) => text,
+                Err(e) => return Some(Err(StreamError::Io(e))),
+            };
+            match text.trim().parse() {
+                Ok(number) => validator.push(number).map(Ok),
+                Err(_) => Some(Err(StreamError::Parse {
+                    line: index + 1,
+                    text,
+                })),
+            }
+        })
+}
 
-    let mut latest_25_numbers: Vec<u64> = numbers[0..25].to_owned();
+struct Xmas {
+    preamble: usize,
+    numbers: Vec<u64>,
+}
 
-    // Task 1
-    for number in &numbers[25..] {
-        if !is_sum_of_numbers(*number, &latest_25_numbers) {
-            println!("is not sum");
-            // Result: 1124361034
-            println!("{}", number);
-            break;
+impl Xmas {
+    fn new(numbers: Vec<u64>, preamble: usize) -> Self {
+        Xmas { preamble, numbers }
+    }
+
+    fn invalid_numbers(&self) -> Vec<u64> {
+        let mut validator = XmasValidator::new(self.preamble);
+        self.numbers
+            .iter()
+            .filter_map(|number| validator.push(*number))
+            .map(|invalid| invalid.value)
+            .collect()
+    }
+
+    // Finds a run of at least two numbers summing up to `target`. Since all numbers are positive
+    // we can keep a window whose sum only grows at the end and shrinks at the start.
+    fn contiguous_range(&self, target: u64) -> Option<&[u64]> {
+        let mut start = 0;
+        let mut sum: u64 = 0;
+
+        for (end, number) in self.numbers.iter().enumerate() {
+            sum += number;
+            while sum > target && start < end {
+                sum -= self.numbers[start];
+                start += 1;
+            }
+            if sum == target && end > start {
+                return Some(&self.numbers[start..=end]);
+            }
         }
 
-        latest_25_numbers.remove(0);
-        latest_25_numbers.push(*number);
+        None
+    }
+
+    fn encryption_weakness(&self, target: u64) -> Option<u64> {
+        let range = self.contiguous_range(target)?;
+        Some(range.iter().min()? + range.iter().max()?)
+    }
+}
+
+fn parse(input: &str) -> Vec<u64> {
+    input.lines().map(str::parse).map(Result::unwrap).collect()
+}
+
+fn main() {
+    // Task 1
+    let file = File::open("./inputs/day09.txt").expect("file not found");
+    for invalid in invalid_numbers_in(BufReader::new(file), 25) {
+        let invalid = invalid.unwrap_or_else(|e| panic!("{}", e));
+        // Result: 1124361034 at position 662
+        println!("{} at position {}", invalid.value, invalid.position);
     }
 
     // Task 2
-    //
-    // Find a sequence of numbers which sum up to 1124361034.
-    // We can keep a window and keep summing it up. If the sum matches: end. If the sum is too big,
-    // remove the first entry.
-    //
-    let target_sum: u64 = 1124361034;
-
-    // Prefill window with first two elements.
-    let mut summands_window: Vec<u64> = numbers[0..2].to_owned();
-
-    for number in &numbers[2..] {
-        let mut sum: u64 = summands_window.iter().sum();
-        if sum == target_sum {
-            println!("found sum");
-            println!("{:?}", summands_window);
-            summands_window.sort();
-            let min = summands_window.iter().nth(0).unwrap();
-            let max = summands_window.last().unwrap();
-            // Result: 129444555
-            println!("min: {}, max: {}, sum: {}", min, max, min + max);
-            break;
-        }
-        summands_window.push(*number);
-        sum = summands_window.iter().sum();
-        // Ensure summands_window has always >= 2 elements.
-        while sum > target_sum && summands_window.len() > 2 {
-            summands_window.remove(0);
-            sum = summands_window.iter().sum();
-        }
+    let content = read_to_string("./inputs/day09.txt").expect("file not found");
+    let xmas = Xmas::new(parse(&content), 25);
+    let target = xmas.invalid_numbers()[0];
+    // Result: 129444555
+    println!("{}", xmas.encryption_weakness(target).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str =
+        "35\n20\n15\n25\n47\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219\n299\n277\n309\n576";
+
+    #[test]
+    fn test_invalid_numbers() {
+        let xmas = Xmas::new(parse(EXAMPLE), 5);
+        assert_eq!(xmas.invalid_numbers(), vec![127]);
+    }
+
+    #[test]
+    fn test_invalid_numbers_in_stream() {
+        let invalid: Vec<InvalidNumber> = invalid_numbers_in(EXAMPLE.as_bytes(), 5)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            invalid,
+            vec![InvalidNumber {
+                position: 14,
+                value: 127
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_numbers_in_malformed_stream() {
+        let invalid: Vec<InvalidNumber> = invalid_numbers_in("1\n2\n\n3\n".as_bytes(), 2)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(invalid, vec![]);
+
+        let results: Vec<_> = invalid_numbers_in("1\n2\n\nx3\n4\n".as_bytes(), 2).collect();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(
+            &results[0],
+            Err(StreamError::Parse { line: 4, text }) if text == "x3"
+        ));
+        assert_eq!(
+            results[0].as_ref().unwrap_err().to_string(),
+            "line 4: not a number: \"x3\""
+        );
+        assert!(matches!(
+            &results[1],
+            Ok(InvalidNumber {
+                position: 2,
+                value: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validator_with_repeated_numbers() {
+        let mut validator = XmasValidator::new(2);
+        assert_eq!(validator.push(5), None);
+        assert_eq!(validator.push(5), None);
+        // 10 = 5 + 5, but the two summands have to be different.
+        assert_eq!(
+            validator.push(10),
+            Some(InvalidNumber {
+                position: 2,
+                value: 10
+            })
+        );
+        assert_eq!(validator.push(15), None);
+        // The window is now 10, 15: 5 dropped out.
+        assert_eq!(
+            validator.push(20),
+            Some(InvalidNumber {
+                position: 4,
+                value: 20
+            })
+        );
+        assert_eq!(validator.push(35), None);
+    }
+
+    #[test]
+    fn test_contiguous_range() {
+        let xmas = Xmas::new(parse(EXAMPLE), 5);
+        assert_eq!(xmas.contiguous_range(127), Some(&[15, 25, 47, 40][..]));
+        assert_eq!(xmas.encryption_weakness(127), Some(62));
+        assert_eq!(xmas.contiguous_range(1), None);
     }
 }
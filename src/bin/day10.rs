@@ -1,63 +1,210 @@
-use std::collections::HashMap;
+use std::env;
 use std::fs::read_to_string;
 
 fn parse(input: &str) -> Vec<u64> {
     input.lines().map(str::parse).map(Result::unwrap).collect()
 }
 
-fn main() {
-    let content = read_to_string("./inputs/day10.txt").expect("file not found");
+// The joltages of a full chain: the charging outlet, all adapters in ascending order and the
+// device, which is rated `max_step` above the highest adapter.
+struct AdapterChain {
+    max_step: u64,
+    joltages: Vec<u64>,
+}
+
+impl AdapterChain {
+    fn new(adapters: &[u64], max_step: u64) -> Self {
+        let mut joltages = adapters.to_vec();
+        joltages.sort_unstable();
+        joltages.insert(0, 0);
+        joltages.push(joltages[joltages.len() - 1] + max_step);
+
+        AdapterChain { max_step, joltages }
+    }
+
+    // Number of joltage differences, indexed by difference.
+    fn difference_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![0; self.max_step as usize + 1];
+
+        for pair in self.joltages.windows(2) {
+            let difference = pair[1] - pair[0];
+            assert!(
+                difference <= self.max_step,
+                "gap of {} between {} and {} can't be bridged",
+                difference,
+                pair[0],
+                pair[1]
+            );
+            histogram[difference as usize] += 1;
+        }
+
+        histogram
+    }
 
-    let mut adapters = parse(&content);
+    // Returns `None` if the count doesn't fit into a `u128`.
+    fn count_arrangements(&self) -> Option<u128> {
+        let joltages = &self.joltages;
+        // count of possibilities to get from index to end
+        let mut dp: Vec<u128> = vec![0; joltages.len()];
+        dp[joltages.len() - 1] = 1;
 
-    adapters.sort();
-    adapters.insert(0, 0);
-    adapters.push(adapters[adapters.len() - 1] + 3);
+        for i in (0..joltages.len() - 1).rev() {
+            dp[i] = (i + 1..joltages.len())
+                .take_while(|j| joltages[*j] - joltages[i] <= self.max_step)
+                .try_fold(0_u128, |sum, j| sum.checked_add(dp[j]))?;
+        }
+
+        Some(dp[0])
+    }
+
+    fn arrangements(&self) -> Arrangements<'_> {
+        Arrangements {
+            chain: self,
+            stack: vec![(0, 1)],
+        }
+    }
+
+    // Adapters which are part of every arrangement, i.e. whose neighbours are too far apart to
+    // connect directly.
+    fn required_adapters(&self) -> Vec<u64> {
+        self.joltages
+            .windows(3)
+            .filter(|w| w[2] - w[0] > self.max_step)
+            .map(|w| w[1])
+            .collect()
+    }
+}
+
+// Lazily walks all arrangements in lexicographic order, yielding the used adapters.
+struct Arrangements<'a> {
+    chain: &'a AdapterChain,
+    // Indices into `chain.joltages` of the current path, each with the next successor to try.
+    stack: Vec<(usize, usize)>,
+}
 
-    let adapters = adapters;
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<u64>;
 
-    let mut differences: (usize, usize, usize) = (0, 0, 0);
+    fn next(&mut self) -> Option<Self::Item> {
+        let joltages = &self.chain.joltages;
+        let device = joltages.len() - 1;
 
-    for x in adapters.windows(2) {
-        if let [first, second] = x {
-            let difference = second - first;
-            if difference == 1 {
-                differences = (differences.0 + 1, differences.1, differences.2);
+        loop {
+            let (current, next) = self.stack.last_mut()?;
+
+            if *current == device {
+                let adapters = self.stack[1..self.stack.len() - 1]
+                    .iter()
+                    .map(|(i, _)| joltages[*i])
+                    .collect();
+                self.stack.pop();
+                return Some(adapters);
             }
-            if difference == 3 {
-                differences = (differences.0, differences.1, differences.2 + 1);
+
+            if *next <= device && joltages[*next] - joltages[*current] <= self.chain.max_step {
+                let successor = *next;
+                *next += 1;
+                self.stack.push((successor, successor + 1));
+            } else {
+                self.stack.pop();
             }
         }
     }
+}
+
+fn main() {
+    let content = read_to_string("./inputs/day10.txt").expect("file not found");
 
-    let (ones, _, threes) = differences;
+    let chain = AdapterChain::new(&parse(&content), 3);
 
+    let histogram = chain.difference_histogram();
     // Result: 2592
-    println!("Part 1: {}", ones * threes);
+    println!("Part 1: {}", histogram[1] * histogram[3]);
 
-    println!("Part 2: {}", part_2(&adapters));
+    // Result: 198428693313536
+    println!("Part 2: {}", chain.count_arrangements().unwrap());
+
+    println!(
+        "Adapters which can't be removed: {:?}",
+        chain.required_adapters()
+    );
+
+    // Usage: cargo run --bin day10 -- [--arrangements <n>] to also list the first n arrangements.
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("--arrangements") {
+        let count = args
+            .next()
+            .and_then(|n| n.parse().ok())
+            .expect("--arrangements needs a number");
+        for arrangement in chain.arrangements().take(count) {
+            println!("{:?}", arrangement);
+        }
+    }
 }
 
-fn part_2(adapters: &[u64]) -> usize {
-    // count of possibilities to get from index to end
-    let mut dp: HashMap<usize, usize> = HashMap::new();
-    dp.insert(adapters.len() - 1, 1);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for i in (0..adapters.len() - 1).rev() {
-        let current_j = adapters[i];
-        let mut possibilities = 0;
+    const SMALL_EXAMPLE: &str = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4";
+    const LARGE_EXAMPLE: &str = "28\n33\n18\n42\n31\n14\n46\n20\n48\n47\n24\n23\n49\n45\n19\n38\n39\n11\n1\n32\n25\n35\n8\n17\n7\n9\n4\n2\n34\n10\n3";
 
-        for step in 1..=3 {
-            if i + step > adapters.len() - 1 {
-                continue;
-            }
+    #[test]
+    fn test_difference_histogram() {
+        let chain = AdapterChain::new(&parse(SMALL_EXAMPLE), 3);
+        assert_eq!(chain.difference_histogram(), vec![0, 7, 0, 5]);
 
-            if adapters[i + step] - current_j <= 3 {
-                possibilities += dp[&(i + step)]
-            }
+        let chain = AdapterChain::new(&parse(LARGE_EXAMPLE), 3);
+        assert_eq!(chain.difference_histogram(), vec![0, 22, 0, 10]);
+    }
+
+    #[test]
+    fn test_count_arrangements() {
+        assert_eq!(
+            AdapterChain::new(&parse(SMALL_EXAMPLE), 3).count_arrangements(),
+            Some(8)
+        );
+        assert_eq!(
+            AdapterChain::new(&parse(LARGE_EXAMPLE), 3).count_arrangements(),
+            Some(19208)
+        );
+        // With a tolerance of 1 there's exactly one way through 1, 2, 3.
+        assert_eq!(
+            AdapterChain::new(&[1, 2, 3], 1).count_arrangements(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_count_arrangements_overflow() {
+        let adapters: Vec<u64> = (1..=200).collect();
+        assert_eq!(AdapterChain::new(&adapters, 3).count_arrangements(), None);
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let chain = AdapterChain::new(&[1, 2, 3], 3);
+        assert_eq!(
+            chain.arrangements().collect::<Vec<_>>(),
+            vec![vec![1, 2, 3], vec![1, 3], vec![2, 3], vec![3],]
+        );
+        assert_eq!(chain.arrangements().take(2).count(), 2);
+    }
+
+    #[test]
+    fn test_arrangements_match_count() {
+        for example in &[SMALL_EXAMPLE, LARGE_EXAMPLE] {
+            let chain = AdapterChain::new(&parse(example), 3);
+            assert_eq!(
+                chain.arrangements().count() as u128,
+                chain.count_arrangements().unwrap()
+            );
         }
-        dp.insert(i, possibilities);
     }
 
-    dp[&0]
+    #[test]
+    fn test_required_adapters() {
+        let chain = AdapterChain::new(&parse(SMALL_EXAMPLE), 3);
+        assert_eq!(chain.required_adapters(), vec![1, 4, 7, 10, 12, 15, 16, 19]);
+    }
 }
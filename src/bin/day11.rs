@@ -1,7 +1,13 @@
-use std::collections::HashSet;
-use std::fs::read_to_string;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 enum TileState {
     Occupied,
     Floor,
@@ -10,6 +16,7 @@ enum TileState {
 
 struct WaitingArea {
     tiles: Vec<Tile>,
+    topology: Topology,
     width: usize,
     height: usize,
 }
@@ -18,244 +25,549 @@ struct WaitingArea {
 struct Tile {
     state: TileState,
     neighbours: Vec<usize>,
+    /// The first seat in each direction. Floor never changes, so these are only computed once.
+    visible_seats: Vec<usize>,
     pos: (usize, usize),
 }
 
-fn clamp(number: usize, min: usize, max: usize) -> usize {
-    if number < min {
-        return min;
-    } else if number > max {
-        return max;
-    }
-    number
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Topology {
+    /// A rectangle with edges, as in the puzzle.
+    Rectangular,
+    /// A rectangle whose opposite edges are glued together.
+    Torus,
+    /// Hexagonal seats, every odd row shifted half a seat to the right.
+    Hex,
 }
 
-fn get_neighbours(position: usize, width: usize, height: usize) -> Vec<usize> {
-    let y = position / width;
-    let x = position - y * width;
-
-    let mut result: HashSet<(usize, usize)> = vec![
-        (clamp(x + 1, 0, width - 1), clamp(y + 1, 0, height - 1)),
-        (x, clamp(y + 1, 0, height - 1)),
-        (clamp(x + 1, 0, width - 1), y),
-        (x.saturating_sub(1), y.saturating_sub(1)),
-        (x.saturating_sub(1), y),
-        (x, y.saturating_sub(1)),
-        (clamp(x + 1, 0, width - 1), y.saturating_sub(1)),
-        (x.saturating_sub(1), clamp(y + 1, 0, height - 1)),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    result.remove(&(x, y));
-    println!("{:?}: {}", (x, y), result.len());
-    println!("{:?}", result);
-
-    result.iter().map(|(x, y)| y * width + x).collect()
+const SQUARE_DIRECTIONS: [(i64, i64); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (0, -1),
+    (1, 1),
+    (1, 0),
+    (0, 1),
+    (-1, 1),
+    (1, -1),
+];
+
+// Axial coordinates, see https://www.redblobgames.com/grids/hexagons/
+const HEX_DIRECTIONS: [(i64, i64); 6] = [(1, 0), (-1, 0), (0, -1), (1, -1), (0, 1), (-1, 1)];
+
+impl Topology {
+    fn directions(&self) -> &'static [(i64, i64)] {
+        match self {
+            Topology::Rectangular | Topology::Torus => &SQUARE_DIRECTIONS,
+            Topology::Hex => &HEX_DIRECTIONS,
+        }
+    }
+
+    /// Moves one step from `pos` into `direction`. Returns `None` when leaving the grid.
+    fn step(
+        &self,
+        pos: (usize, usize),
+        direction: (i64, i64),
+        width: usize,
+        height: usize,
+    ) -> Option<(usize, usize)> {
+        let (width, height) = (width as i64, height as i64);
+        let (x, y) = (pos.0 as i64, pos.1 as i64);
+        let (x, y) = match self {
+            Topology::Rectangular => (x + direction.0, y + direction.1),
+            Topology::Torus => (
+                (x + direction.0).rem_euclid(width),
+                (y + direction.1).rem_euclid(height),
+            ),
+            Topology::Hex => {
+                let q = x - (y - (y & 1)) / 2 + direction.0;
+                let r = y + direction.1;
+                (q + (r - (r & 1)) / 2, r)
+            }
+        };
+        if (0..width).contains(&x) && (0..height).contains(&y) {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
 }
 
-fn neighbour_states(tile: &Tile, tiles: &[Tile]) -> Vec<TileState> {
-    tile.neighbours
+fn get_neighbours(position: usize, topology: Topology, width: usize, height: usize) -> Vec<usize> {
+    let pos = (position % width, position / width);
+
+    topology
+        .directions()
         .iter()
-        .map(|n| &tiles[*n].state)
-        .cloned()
+        .filter_map(|dir| topology.step(pos, *dir, width, height))
+        .filter(|neighbour| *neighbour != pos)
+        .map(|(x, y)| y * width + x)
         .collect()
 }
 
-fn simulate_rule(tiles: &[Tile]) -> Vec<Tile> {
-    let result = tiles
+fn get_visible_seats(
+    position: usize,
+    topology: Topology,
+    width: usize,
+    height: usize,
+    states: &[TileState],
+) -> Vec<usize> {
+    let start = (position % width, position / width);
+
+    topology
+        .directions()
         .iter()
-        .map(|tile| match tile.state {
-            TileState::Empty
-                if neighbour_states(&tile, tiles)
-                    .iter()
-                    .all(|s| *s != TileState::Occupied) =>
-            {
-                Tile {
-                    neighbours: tile.neighbours.clone(),
-                    state: TileState::Occupied,
-                    pos: tile.pos.clone(),
-                }
-            }
-            TileState::Occupied
-                if neighbour_states(&tile, tiles)
-                    .iter()
-                    .filter(|s| **s == TileState::Occupied)
-                    .count()
-                    >= 4 =>
-            {
-                Tile {
-                    neighbours: tile.neighbours.clone(),
-                    state: TileState::Empty,
-                    pos: tile.pos.clone(),
-                }
-            }
-            _ => tile.clone(),
+        .filter_map(|dir| {
+            // On a torus a ray can wrap around, so stop once it's back at the start.
+            std::iter::successors(Some(start), |pos| topology.step(*pos, *dir, width, height))
+                .skip(1)
+                .take_while(|pos| *pos != start)
+                .take(width * height)
+                .map(|(x, y)| y * width + x)
+                .find(|i| states[*i] != TileState::Floor)
         })
-        .collect();
-    result
+        .collect()
 }
 
-fn simulate_rule_2(waiting_area: &WaitingArea) -> WaitingArea {
-    let result = waiting_area
-        .tiles
-        .iter()
-        .map(|tile| match tile.state {
-            TileState::Empty if seen_seats(tile.pos, waiting_area).occupied == 0 => Tile {
-                neighbours: tile.neighbours.clone(),
-                state: TileState::Occupied,
-                pos: tile.pos.clone(),
-            },
-            TileState::Occupied if seen_seats(tile.pos, waiting_area).occupied >= 5 => Tile {
-                neighbours: tile.neighbours.clone(),
-                state: TileState::Empty,
-                pos: tile.pos.clone(),
-            },
-            _ => tile.clone(),
-        })
-        .collect();
+#[derive(Debug, Clone, Copy)]
+enum Neighbourhood {
+    /// The tiles directly surrounding a seat.
+    Adjacent,
+    /// The first seat visible in each direction.
+    LineOfSight,
+}
 
-    WaitingArea {
-        width: waiting_area.width,
-        height: waiting_area.height,
-        tiles: result,
-    }
+/// Decides how seats change from one generation to the next.
+struct SeatingRule {
+    neighbourhood: Neighbourhood,
+    /// An occupied seat is left if at least this many neighbours are occupied.
+    crowding_threshold: usize,
+    /// An empty seat is taken if at most this many neighbours are occupied.
+    sit_down_threshold: usize,
 }
 
-fn walk_till_seat(
-    pos: (usize, usize),
-    dir: &(i32, i32),
-    waiting_area: &WaitingArea,
-) -> Option<TileState> {
-    let WaitingArea {
-        width,
-        height,
-        tiles,
-    } = waiting_area;
-    let mut new_pos: (i32, i32) = (pos.0 as i32, pos.1 as i32);
-    loop {
-        new_pos = (new_pos.0 + dir.0, new_pos.1 + dir.1);
-        if new_pos.0 >= *width as i32 {
-            return None;
-        }
-        if new_pos.0 < 0 || new_pos.1 < 0 {
-            return None;
-        }
-        if new_pos.1 >= *height as i32 {
-            return None;
-        }
-        if tiles[new_pos.1 as usize * width + new_pos.0 as usize].state == TileState::Occupied {
-            return Some(TileState::Occupied);
+const PART_1_RULE: SeatingRule = SeatingRule {
+    neighbourhood: Neighbourhood::Adjacent,
+    crowding_threshold: 4,
+    sit_down_threshold: 0,
+};
+
+const PART_2_RULE: SeatingRule = SeatingRule {
+    neighbourhood: Neighbourhood::LineOfSight,
+    crowding_threshold: 5,
+    sit_down_threshold: 0,
+};
+
+impl WaitingArea {
+    fn parse(content: &str, topology: Topology) -> Self {
+        let character_count = content.chars().filter(|x| *x != '\n').count();
+        let width = content.find('\n').unwrap();
+        let height = character_count / width;
+
+        let states: Vec<TileState> = content
+            .chars()
+            .filter(|x| *x != '\n')
+            .map(|x| match x {
+                'L' => TileState::Empty,
+                '.' => TileState::Floor,
+                '#' => TileState::Occupied,
+                _ => panic!("unknown tile {}", x),
+            })
+            .collect();
+
+        let tiles = states
+            .iter()
+            .enumerate()
+            .map(|(i, state)| Tile {
+                state: *state,
+                neighbours: get_neighbours(i, topology, width, height),
+                visible_seats: get_visible_seats(i, topology, width, height, &states),
+                pos: (i % width, i / width),
+            })
+            .collect();
+
+        WaitingArea {
+            tiles,
+            topology,
+            width,
+            height,
         }
-        if tiles[new_pos.1 as usize * width + new_pos.0 as usize].state == TileState::Empty {
-            return Some(TileState::Empty);
+    }
+
+    fn states(&self) -> Vec<TileState> {
+        self.tiles.iter().map(|t| t.state).collect()
+    }
+
+    fn neighbours(&self, index: usize, neighbourhood: Neighbourhood) -> &[usize] {
+        match neighbourhood {
+            Neighbourhood::Adjacent => &self.tiles[index].neighbours,
+            Neighbourhood::LineOfSight => &self.tiles[index].visible_seats,
         }
     }
 }
 
-struct SeatCounts {
+/// Computes the generation following `current` into `next`. Returns whether any seat changed.
+fn simulate(
+    waiting_area: &WaitingArea,
+    rule: &SeatingRule,
+    current: &[TileState],
+    next: &mut [TileState],
+) -> bool {
+    let mut changed = false;
+
+    for (i, state) in current.iter().enumerate() {
+        let occupied = || {
+            waiting_area
+                .neighbours(i, rule.neighbourhood)
+                .iter()
+                .filter(|n| current[**n] == TileState::Occupied)
+                .count()
+        };
+        next[i] = match state {
+            TileState::Empty if occupied() <= rule.sit_down_threshold => TileState::Occupied,
+            TileState::Occupied if occupied() >= rule.crowding_threshold => TileState::Empty,
+            _ => *state,
+        };
+        changed |= next[i] != *state;
+    }
+
+    changed
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Generation `generation` is the first one which doesn't change anymore.
+    Stable {
+        generation: usize,
+    },
+    /// Generation `start` reappears every `period` generations.
+    Cycle {
+        start: usize,
+        period: usize,
+    },
+    MaxGenerations,
+}
+
+#[derive(Debug, PartialEq)]
+struct SimulationResult {
+    outcome: Outcome,
+    /// Occupied seats in the last simulated generation.
     occupied: usize,
-    free: usize,
 }
 
-/// Returns the amount of empty seats and occupied seats seen from `position`.
-fn seen_seats(position: (usize, usize), waiting_area: &WaitingArea) -> SeatCounts {
-    let directions = vec![
-        (-1, -1),
-        (-1, 0),
-        (0, -1),
-        (1, 1),
-        (1, 0),
-        (0, 1),
-        (-1, 1),
-        (1, -1),
-    ];
-    let mut result = SeatCounts {
-        occupied: 0,
-        free: 0,
-    };
-    for dir in directions {
-        match walk_till_seat(position, &dir, waiting_area) {
-            Some(TileState::Occupied) => result.occupied += 1,
-            Some(TileState::Empty) => result.free += 1,
-            _ => {}
+impl fmt::Display for SimulationResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} occupied, ", self.occupied)?;
+        match self.outcome {
+            Outcome::Stable { generation } => write!(f, "stable from generation {}", generation),
+            Outcome::Cycle { start, period } => {
+                write!(f, "cycle of period {} from generation {}", period, start)
+            }
+            Outcome::MaxGenerations => write!(f, "still changing at the generation limit"),
         }
     }
-    result
 }
 
-fn main() {
-    let content = read_to_string("./inputs/day11.txt").expect("file not found");
+/// Simulates until the seats stop changing, a previous generation repeats or `max_generations`
+/// generations have been computed.
+fn run(waiting_area: &WaitingArea, rule: &SeatingRule, max_generations: usize) -> SimulationResult {
+    let mut current = waiting_area.states();
+    let mut next = current.clone();
+    let mut seen: HashMap<Vec<TileState>, usize> = HashMap::new();
+
+    let outcome = (0..max_generations)
+        .find_map(|generation| {
+            if !simulate(waiting_area, rule, &current, &mut next) {
+                return Some(Outcome::Stable { generation });
+            }
+            if let Some(start) = seen.insert(current.clone(), generation) {
+                return Some(Outcome::Cycle {
+                    start,
+                    period: generation - start,
+                });
+            }
+            std::mem::swap(&mut current, &mut next);
+            None
+        })
+        .unwrap_or(Outcome::MaxGenerations);
+
+    SimulationResult {
+        outcome,
+        occupied: current
+            .iter()
+            .filter(|s| **s == TileState::Occupied)
+            .count(),
+    }
+}
 
-    let character_count = content.chars().filter(|x| *x != '\n').count();
-    let width = content.find('\n').unwrap();
-    let height = character_count / width;
+/// Yields the initial seats and every following generation up to the first stable one.
+fn generations<'a>(
+    waiting_area: &'a WaitingArea,
+    rule: &'a SeatingRule,
+) -> impl Iterator<Item = Vec<TileState>> + 'a {
+    let mut current = Some(waiting_area.states());
+    std::iter::from_fn(move || {
+        let states = current.take()?;
+        let mut next = states.clone();
+        if simulate(waiting_area, rule, &states, &mut next) {
+            current = Some(next);
+        }
+        Some(states)
+    })
+}
 
-    let tiles: Vec<Tile> = content
-        .chars()
-        .filter(|x| *x != '\n')
+fn render_ascii(waiting_area: &WaitingArea, states: &[TileState]) -> String {
+    states
+        .chunks(waiting_area.width)
         .enumerate()
-        .map(|(i, x)| {
-            let state = match x {
-                'L' => TileState::Empty,
-                '.' => TileState::Floor,
-                '#' => TileState::Occupied,
-                _ => panic!("unknown tile {}", x),
+        .map(|(y, row)| {
+            let indent = if waiting_area.topology == Topology::Hex && y % 2 == 1 {
+                " "
+            } else {
+                ""
             };
-            let neighbours = get_neighbours(i, width, height);
-            let y = i / width;
-            let x = i - y * width;
-            Tile {
-                state,
-                neighbours,
-                pos: (x, y),
-            }
+            let seats: String = row
+                .iter()
+                .map(|s| match s {
+                    TileState::Empty => 'L',
+                    TileState::Floor => '.',
+                    TileState::Occupied => '#',
+                })
+                .collect();
+            format!("{}{}", indent, seats)
         })
-        .collect();
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    let waiting_area = WaitingArea {
-        tiles: tiles,
-        width,
-        height,
-    };
+fn colour(state: TileState) -> [u8; 3] {
+    match state {
+        TileState::Floor => [40, 40, 40],
+        TileState::Empty => [60, 180, 75],
+        TileState::Occupied => [230, 25, 75],
+    }
+}
 
-    // Task 1
-    let mut old_tiles = waiting_area.tiles.clone();
-    loop {
-        let new_tiles = simulate_rule(&old_tiles);
-        if new_tiles == old_tiles {
-            println!("finish!");
-            // Result: 2183
-            println!(
-                "{}",
-                new_tiles
-                    .iter()
-                    .filter(|t| t.state == TileState::Occupied)
-                    .count()
-            );
-            break;
+/// Writes the seats as a binary PPM image, drawing every tile as a `scale` x `scale` square.
+fn write_ppm(
+    path: &Path,
+    waiting_area: &WaitingArea,
+    states: &[TileState],
+    scale: usize,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(
+        file,
+        "P6\n{} {}\n255",
+        waiting_area.width * scale,
+        waiting_area.height * scale
+    )?;
+    for row in states.chunks(waiting_area.width) {
+        for _ in 0..scale {
+            for state in row {
+                for _ in 0..scale {
+                    file.write_all(&colour(*state))?;
+                }
+            }
         }
-        old_tiles = new_tiles;
     }
+    file.flush()
+}
+
+/// Writes one `frame_NNNN.ppm` per generation into `directory`. Returns the number of frames.
+fn export_frames(
+    waiting_area: &WaitingArea,
+    rule: &SeatingRule,
+    directory: &Path,
+    max_generations: usize,
+) -> io::Result<usize> {
+    create_dir_all(directory)?;
+    let mut frames = 0;
+    for (generation, states) in generations(waiting_area, rule)
+        .take(max_generations)
+        .enumerate()
+    {
+        let path = directory.join(format!("frame_{:04}.ppm", generation));
+        write_ppm(&path, waiting_area, &states, 8)?;
+        frames += 1;
+    }
+    Ok(frames)
+}
+
+/// Redraws every generation in the terminal, waiting `delay` between frames.
+fn animate_ascii(
+    waiting_area: &WaitingArea,
+    rule: &SeatingRule,
+    delay: Duration,
+    max_generations: usize,
+) {
+    for (generation, states) in generations(waiting_area, rule)
+        .take(max_generations)
+        .enumerate()
+    {
+        // Clear the screen and move the cursor to the top left.
+        print!("\x1b[2J\x1b[H");
+        println!("generation {}", generation);
+        println!("{}", render_ascii(waiting_area, &states));
+        sleep(delay);
+    }
+}
+
+fn main() {
+    let content = read_to_string("./inputs/day11.txt").expect("file not found");
+    let waiting_area = WaitingArea::parse(&content, Topology::Rectangular);
+
+    // Task 1
+    // Result: 2183
+    println!("{}", run(&waiting_area, &PART_1_RULE, 1000).occupied);
 
     // Task 2
-    let mut old_waiting_area = waiting_area;
-    loop {
-        let new_waiting_area = simulate_rule_2(&old_waiting_area);
-        if new_waiting_area.tiles == old_waiting_area.tiles {
-            println!("finish!");
-            // Result: 1990
-            println!(
-                "{}",
-                new_waiting_area
-                    .tiles
-                    .iter()
-                    .filter(|t| t.state == TileState::Occupied)
-                    .count()
-            );
-            break;
+    // Result: 1990
+    println!("{}", run(&waiting_area, &PART_2_RULE, 1000).occupied);
+
+    // Other topologies, compared with both rules unless rendering is asked for:
+    //   cargo run --bin day11 -- --torus|--hex
+    //
+    // Rendering:
+    //   cargo run --bin day11 -- --ppm <directory> [--part2] [--torus|--hex]
+    //   cargo run --bin day11 -- --ascii <delay in ms> [--part2] [--torus|--hex]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rule = if args.iter().any(|a| a == "--part2") {
+        &PART_2_RULE
+    } else {
+        &PART_1_RULE
+    };
+    let topology = if args.iter().any(|a| a == "--torus") {
+        Topology::Torus
+    } else if args.iter().any(|a| a == "--hex") {
+        Topology::Hex
+    } else {
+        Topology::Rectangular
+    };
+    let waiting_area = WaitingArea::parse(&content, topology);
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("--ppm"), Some(directory)) => {
+            let frames = export_frames(&waiting_area, rule, Path::new(directory), 1000)
+                .expect("failed to write frames");
+            println!("wrote {} frames to {}", frames, directory);
+        }
+        (Some("--ascii"), delay) => {
+            let delay = delay.map_or(100, |d| d.parse().expect("delay in ms"));
+            animate_ascii(&waiting_area, rule, Duration::from_millis(delay), 1000);
         }
-        old_waiting_area = new_waiting_area;
+        _ if topology != Topology::Rectangular => {
+            for (part, rule) in [(1, &PART_1_RULE), (2, &PART_2_RULE)] {
+                let result = run(&waiting_area, rule, 1000);
+                println!("{:?}, part {}: {}", topology, part, result);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL
+";
+
+    #[test]
+    fn test_stable() {
+        let waiting_area = WaitingArea::parse(EXAMPLE, Topology::Rectangular);
+        assert_eq!(
+            run(&waiting_area, &PART_1_RULE, 100),
+            SimulationResult {
+                outcome: Outcome::Stable { generation: 5 },
+                occupied: 37
+            }
+        );
+        assert_eq!(run(&waiting_area, &PART_2_RULE, 100).occupied, 26);
+    }
+
+    #[test]
+    fn test_generations() {
+        let waiting_area = WaitingArea::parse(EXAMPLE, Topology::Rectangular);
+        let generations: Vec<_> = generations(&waiting_area, &PART_1_RULE).collect();
+        assert_eq!(generations.len(), 6);
+        assert_eq!(
+            render_ascii(&waiting_area, &generations[1]),
+            "#.##.##.##
+#######.##
+#.#.#..#..
+####.##.##
+#.##.##.##
+#.#####.##
+..#.#.....
+##########
+#.######.#
+#.#####.##"
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        // Every empty seat is taken, every seat with an occupied neighbour is left.
+        let rule = SeatingRule {
+            neighbourhood: Neighbourhood::Adjacent,
+            crowding_threshold: 1,
+            sit_down_threshold: 8,
+        };
+        let waiting_area = WaitingArea::parse("LL\n", Topology::Rectangular);
+        assert_eq!(
+            run(&waiting_area, &rule, 100),
+            SimulationResult {
+                outcome: Outcome::Cycle {
+                    start: 0,
+                    period: 2
+                },
+                occupied: 0
+            }
+        );
+        assert_eq!(
+            run(&waiting_area, &rule, 1),
+            SimulationResult {
+                outcome: Outcome::MaxGenerations,
+                occupied: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_neighbours() {
+        // Corner, edge and interior tile of a 3x3 grid.
+        assert_eq!(get_neighbours(0, Topology::Rectangular, 3, 3).len(), 3);
+        assert_eq!(get_neighbours(1, Topology::Rectangular, 3, 3).len(), 5);
+        assert_eq!(get_neighbours(4, Topology::Rectangular, 3, 3).len(), 8);
+
+        let mut torus_corner = get_neighbours(0, Topology::Torus, 3, 3);
+        torus_corner.sort_unstable();
+        assert_eq!(torus_corner, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Row 1 is shifted to the right, so (1, 1) touches (1, 0), (2, 0), (1, 2) and (2, 2).
+        let mut hex_center = get_neighbours(4, Topology::Hex, 3, 3);
+        hex_center.sort_unstable();
+        assert_eq!(hex_center, vec![1, 2, 3, 5, 7, 8]);
+        let mut hex_corner = get_neighbours(0, Topology::Hex, 3, 3);
+        hex_corner.sort_unstable();
+        assert_eq!(hex_corner, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_visible_seats_wrap_on_torus() {
+        // With a single row every ray except the vertical ones ends up at the other seat.
+        let waiting_area = WaitingArea::parse("L.L.\n", Topology::Torus);
+        assert_eq!(waiting_area.tiles[0].visible_seats, vec![2; 6]);
+
+        let waiting_area = WaitingArea::parse("L...\n", Topology::Torus);
+        assert_eq!(waiting_area.tiles[0].visible_seats, Vec::<usize>::new());
     }
 }
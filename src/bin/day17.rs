@@ -1,328 +1,669 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fmt;
-use std::fs::read_to_string;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[path = "../life_rule.rs"]
+mod life_rule;
+use life_rule::Rule;
+
+type Coordinate<const N: usize> = [i64; N];
 
 #[derive(Clone)]
-struct World3d {
-    actives: HashSet<(i64, i64, i64)>,
+struct World<const N: usize> {
+    actives: HashSet<Coordinate<N>>,
 }
 
-impl fmt::Display for World3d {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let bounding_box = Aabb::<(i64, i64, i64)>::from_coordinates(&self.actives);
+// Names of the dimensions beyond x and y, as used in the puzzle text.
+const DIMENSION_NAMES: [&str; 4] = ["z", "w", "v", "u"];
 
-        for z in bounding_box.min.2..=bounding_box.max.2 {
-            write!(f, "z = {}\n", z)?;
-            for y in bounding_box.min.1..=bounding_box.max.1 {
-                for x in bounding_box.min.0..=bounding_box.max.0 {
-                    if self.actives.contains(&(x, y, z)) {
+impl<const N: usize> fmt::Display for World<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.actives.is_empty() {
+            return Ok(());
+        }
+        let bounding_box = Aabb::from_coordinates(&self.actives);
+
+        // Every combination of the dimensions beyond x and y is one plane.
+        let mut planes = bounding_box;
+        planes.max[0] = planes.min[0];
+        planes.max[1] = planes.min[1];
+        for mut c in planes.points() {
+            let label: Vec<String> = c[2..]
+                .iter()
+                .enumerate()
+                .map(|(i, v)| format!("{}={}", DIMENSION_NAMES.get(i).unwrap_or(&"?"), v))
+                .collect();
+            writeln!(f, "{}", label.join(", "))?;
+
+            for y in bounding_box.min[1]..=bounding_box.max[1] {
+                for x in bounding_box.min[0]..=bounding_box.max[0] {
+                    c[0] = x;
+                    c[1] = y;
+                    if self.actives.contains(&c) {
                         write!(f, "#")?
                     } else {
                         write!(f, ".")?
                     }
                 }
-                write!(f, "\n")?
+                writeln!(f)?
             }
+            writeln!(f)?
         }
         Ok(())
     }
 }
 
-#[derive(Clone)]
-struct World4d {
-    actives: HashSet<(i64, i64, i64, i64)>,
-}
-impl fmt::Display for World4d {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Ok(())
-    }
-}
-
-trait World {
-    type Coordinate;
-
-    fn active_neighbours_count(&self, c: &Self::Coordinate) -> usize;
-
-    fn points_to_check(&self) -> Vec<Self::Coordinate>;
-
-    fn actives_count(&self) -> usize;
-
-    fn cube_state(&self, c: &Self::Coordinate) -> State;
-
-    fn insert_active(&mut self, c: Self::Coordinate);
-    fn remove_active(&mut self, c: &Self::Coordinate);
-}
+impl<const N: usize> World<N> {
+    /// Places the 2D slice `content` at the origin of all further dimensions.
+    fn parse(content: &str) -> Self {
+        let actives = content
+            .lines()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars().enumerate().filter_map(move |(x, c)| {
+                    if parse_state(c) == State::Active {
+                        let mut coordinate = [0; N];
+                        coordinate[0] = x as i64;
+                        coordinate[1] = y as i64;
+                        Some(coordinate)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .collect();
 
-impl World for World3d {
-    type Coordinate = (i64, i64, i64);
+        World { actives }
+    }
 
-    fn active_neighbours_count(&self, c: &Self::Coordinate) -> usize {
-        let mut neighbours = vec![];
+    /// A header line `day17 <dimensions> <cycle>` followed by one active cube per line, with its
+    /// coordinates separated by commas.
+    fn to_snapshot(&self, cycle: usize) -> String {
+        let mut actives: Vec<&Coordinate<N>> = self.actives.iter().collect();
+        actives.sort_unstable();
+
+        let mut snapshot = format!("day17 {} {}\n", N, cycle);
+        for c in actives {
+            let coordinates: Vec<String> = c.iter().map(|v| v.to_string()).collect();
+            snapshot.push_str(&coordinates.join(","));
+            snapshot.push('\n');
+        }
+        snapshot
+    }
 
-        for x in -1..=1 {
-            for y in -1..=1 {
-                for z in -1..=1 {
-                    if (x, y, z) != (0, 0, 0) {
-                        neighbours.push((c.0 + x, c.1 + y, c.2 + z));
-                    }
-                }
-            }
+    /// Reads a snapshot written by `to_snapshot`, returning the world and its cycle.
+    fn from_snapshot(snapshot: &str) -> Result<(Self, usize), String> {
+        let mut lines = snapshot.lines();
+        let header: Vec<&str> = lines.next().ok_or("empty snapshot")?.split(' ').collect();
+        let (dimensions, cycle) = match header[..] {
+            ["day17", dimensions, cycle] => (
+                dimensions.parse::<usize>().map_err(|e| e.to_string())?,
+                cycle.parse::<usize>().map_err(|e| e.to_string())?,
+            ),
+            _ => return Err(format!("invalid snapshot header {:?}", header.join(" "))),
+        };
+        if dimensions != N {
+            return Err(format!(
+                "snapshot has {} dimensions, expected {}",
+                dimensions, N
+            ));
         }
 
-        neighbours
+        let actives = lines
+            .map(|line| {
+                let coordinates = line
+                    .split(',')
+                    .map(|v| v.parse().map_err(|_| format!("invalid cube {:?}", line)))
+                    .collect::<Result<Vec<i64>, String>>()?;
+                coordinates
+                    .try_into()
+                    .map_err(|_| format!("invalid cube {:?}", line))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok((World { actives }, cycle))
+    }
+
+    fn active_neighbours_count(&self, c: &Coordinate<N>, offsets: &[Coordinate<N>]) -> usize {
+        offsets
             .iter()
-            .filter(|c| self.actives.contains(c))
+            .filter(|offset| self.actives.contains(&add(c, offset)))
             .count()
     }
 
-    fn cube_state(&self, c: &Self::Coordinate) -> State {
-        if self.actives.contains(&c) {
+    fn points_to_check(&self) -> Vec<Coordinate<N>> {
+        Aabb::from_coordinates(&self.actives)
+            .padded(1)
+            .points()
+            .collect()
+    }
+
+    fn cube_state(&self, c: &Coordinate<N>) -> State {
+        if self.actives.contains(c) {
             State::Active
         } else {
             State::Inactive
         }
     }
 
-    fn insert_active(&mut self, c: Self::Coordinate) {
+    fn insert_active(&mut self, c: Coordinate<N>) {
         self.actives.insert(c);
     }
 
-    fn remove_active(&mut self, c: &Self::Coordinate) {
+    fn remove_active(&mut self, c: &Coordinate<N>) {
         self.actives.remove(c);
     }
 
-    fn points_to_check(&self) -> Vec<Self::Coordinate> {
-        let mut min = (i64::MAX, i64::MAX, i64::MAX);
-        let mut max = (i64::MIN, i64::MIN, i64::MIN);
+    fn actives_count(&self) -> usize {
+        self.actives.len()
+    }
+}
 
-        for (x, y, z) in self.actives.clone() {
-            if x < min.0 {
-                min.0 = x;
-            }
-            if y < min.1 {
-                min.1 = y;
-            }
-            if z < min.2 {
-                min.2 = z;
-            }
-            if x > max.0 {
-                max.0 = x;
-            }
-            if y > max.1 {
-                max.1 = y;
-            }
-            if z > max.2 {
-                max.2 = z;
+fn add<const N: usize>(a: &Coordinate<N>, b: &Coordinate<N>) -> Coordinate<N> {
+    let mut result = *a;
+    for (r, b) in result.iter_mut().zip(b) {
+        *r += b;
+    }
+    result
+}
+
+/// All 3^N - 1 offsets to the neighbours of a cube.
+fn neighbour_offsets<const N: usize>() -> impl Iterator<Item = Coordinate<N>> {
+    let cube = Aabb {
+        min: [-1; N],
+        max: [1; N],
+    };
+    cube.points()
+        .filter(|offset| offset.iter().any(|v| *v != 0))
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum State {
+    Active,
+    Inactive,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Aabb<const N: usize> {
+    min: Coordinate<N>,
+    max: Coordinate<N>,
+}
+
+impl<const N: usize> Aabb<N> {
+    fn from_coordinates(coordinates: &HashSet<Coordinate<N>>) -> Self {
+        let mut min = [i64::MAX; N];
+        let mut max = [i64::MIN; N];
+
+        for c in coordinates {
+            for i in 0..N {
+                min[i] = min[i].min(c[i]);
+                max[i] = max[i].max(c[i]);
             }
         }
-        let aabb = Aabb { min, max };
+        Self { min, max }
+    }
+
+    fn padded(&self, by: i64) -> Self {
+        Self {
+            min: self.min.map(|v| v - by),
+            max: self.max.map(|v| v + by),
+        }
+    }
 
-        let mut result = Vec::new();
-        for x in aabb.min.0 - 1..=aabb.max.0 + 1 {
-            for y in aabb.min.1 - 1..=aabb.max.1 + 1 {
-                for z in aabb.min.2 - 1..=aabb.max.2 + 1 {
-                    result.push((x, y, z));
+    /// Iterates over all points inside the box, including its borders.
+    fn points(self) -> impl Iterator<Item = Coordinate<N>> {
+        let mut next = if self.min.iter().zip(&self.max).all(|(min, max)| min <= max) {
+            Some(self.min)
+        } else {
+            None
+        };
+
+        std::iter::from_fn(move || {
+            let current = next.take()?;
+            let mut following = current;
+            for i in 0..N {
+                if following[i] < self.max[i] {
+                    following[i] += 1;
+                    next = Some(following);
+                    break;
                 }
+                following[i] = self.min[i];
             }
-        }
-        result
+            Some(current)
+        })
+    }
+}
+
+fn parse_state(c: char) -> State {
+    match c {
+        '.' => State::Inactive,
+        '#' => State::Active,
+        _ => panic!("not a valid character: {}", c),
     }
+}
 
-    fn actives_count(&self) -> usize {
-        self.actives.len()
+const CONWAY: &str = "B3/S23";
+
+fn next_state(rule: &Rule, state: State, active_neighbours: usize) -> State {
+    let counts = match state {
+        State::Active => &rule.survive,
+        State::Inactive => &rule.born,
+    };
+    if counts.contains(&active_neighbours) {
+        State::Active
+    } else {
+        State::Inactive
     }
 }
 
-impl World for World4d {
-    type Coordinate = (i64, i64, i64, i64);
+#[derive(Debug, Clone, Copy)]
+enum Algorithm {
+    /// Checks every point of the bounding box grown by one.
+    BoundingBox,
+    /// Lets every active cube add one to the tally of each of its neighbours.
+    NeighbourTally,
+    /// Like `NeighbourTally`, but only simulates the half-space where all dimensions beyond x and
+    /// y are non-negative. Requires a world which is mirror-symmetric in those dimensions, e.g.
+    /// one starting as a single slice.
+    MirrorSymmetric,
+}
 
-    fn active_neighbours_count(&self, c: &Self::Coordinate) -> usize {
-        let mut neighbours = vec![];
+/// Maps a cube onto its mirror image in the non-negative half-space.
+fn canonical<const N: usize>(c: &Coordinate<N>) -> Coordinate<N> {
+    let mut result = *c;
+    for v in &mut result[2..] {
+        *v = v.abs();
+    }
+    result
+}
 
-        for x in -1..=1 {
-            for y in -1..=1 {
-                for z in -1..=1 {
-                    for w in -1..=1 {
-                        if (x, y, z, w) != (0, 0, 0, 0) {
-                            neighbours.push((c.0 + x, c.1 + y, c.2 + z, c.3 + w));
-                        }
-                    }
-                }
+/// Number of mirror images of a cube, including itself.
+fn multiplicity<const N: usize>(c: &Coordinate<N>) -> usize {
+    1 << c[2..].iter().filter(|v| **v != 0).count()
+}
+
+fn mirror_images<const N: usize>(c: &Coordinate<N>) -> Vec<Coordinate<N>> {
+    let mut result = vec![*c];
+    for i in 2..N {
+        if c[i] != 0 {
+            let flipped: Vec<_> = result
+                .iter()
+                .map(|m| {
+                    let mut m = *m;
+                    m[i] = -m[i];
+                    m
+                })
+                .collect();
+            result.extend(flipped);
+        }
+    }
+    result
+}
+
+impl<const N: usize> World<N> {
+    fn step_bounding_box(&self, rule: &Rule, offsets: &[Coordinate<N>]) -> Self {
+        let mut world = self.clone();
+
+        for c in self.points_to_check() {
+            let active_neighbours = self.active_neighbours_count(&c, offsets);
+            match next_state(rule, self.cube_state(&c), active_neighbours) {
+                State::Active => world.insert_active(c),
+                State::Inactive => world.remove_active(&c),
             }
         }
 
-        neighbours
+        world
+    }
+
+    fn step_neighbour_tally(&self, rule: &Rule, offsets: &[Coordinate<N>]) -> Self {
+        let mut tally: HashMap<Coordinate<N>, usize> = HashMap::new();
+        for c in &self.actives {
+            for offset in offsets {
+                *tally.entry(add(c, offset)).or_insert(0) += 1;
+            }
+        }
+
+        // Cubes without any active neighbours aren't part of the tally, so they are handled
+        // separately.
+        let survivors = self
+            .actives
             .iter()
-            .filter(|c| self.actives.contains(c))
-            .count()
+            .filter(|c| !tally.contains_key(*c) && rule.survive.contains(&0))
+            .copied()
+            .collect::<Vec<_>>();
+        let actives = tally
+            .into_iter()
+            .filter(|(c, count)| next_state(rule, self.cube_state(c), *count) == State::Active)
+            .map(|(c, _)| c)
+            .chain(survivors)
+            .collect();
+
+        World { actives }
     }
 
-    fn cube_state(&self, c: &Self::Coordinate) -> State {
-        if self.actives.contains(&c) {
-            State::Active
-        } else {
-            State::Inactive
+    // Works on the half-space representation. The orbit of a source cube `c` contributes
+    // `multiplicity(c) / multiplicity(t)` to a target `t` for every neighbour of `c` which is a
+    // mirror image of `t`. The tally is kept multiplied by `multiplicity(t)` to stay exact.
+    fn step_mirror_symmetric(&self, rule: &Rule, offsets: &[Coordinate<N>]) -> Self {
+        let mut tally: HashMap<Coordinate<N>, usize> = HashMap::new();
+        for c in &self.actives {
+            let weight = multiplicity(c);
+            for offset in offsets {
+                *tally.entry(canonical(&add(c, offset))).or_insert(0) += weight;
+            }
         }
+
+        let survivors = self
+            .actives
+            .iter()
+            .filter(|c| !tally.contains_key(*c) && rule.survive.contains(&0))
+            .copied()
+            .collect::<Vec<_>>();
+        let actives = tally
+            .into_iter()
+            .filter(|(c, count)| {
+                next_state(rule, self.cube_state(c), *count / multiplicity(c)) == State::Active
+            })
+            .map(|(c, _)| c)
+            .chain(survivors)
+            .collect();
+
+        World { actives }
     }
 
-    fn insert_active(&mut self, c: Self::Coordinate) {
-        self.actives.insert(c);
+    /// Turns a half-space world back into the full world.
+    fn expanded(&self) -> Self {
+        World {
+            actives: self.actives.iter().flat_map(mirror_images).collect(),
+        }
     }
 
-    fn remove_active(&mut self, c: &Self::Coordinate) {
-        self.actives.remove(c);
+    fn is_mirror_symmetric(&self) -> bool {
+        self.actives
+            .iter()
+            .all(|c| mirror_images(c).iter().all(|m| self.actives.contains(m)))
     }
+}
 
-    fn points_to_check(&self) -> Vec<Self::Coordinate> {
-        let mut min = (i64::MAX, i64::MAX, i64::MAX, i64::MAX);
-        let mut max = (i64::MIN, i64::MIN, i64::MIN, i64::MIN);
+/// Simulates the given cycles, printing every resulting world if `verbose` is set.
+fn solve<const N: usize>(
+    world: &mut World<N>,
+    rule: &Rule,
+    cycles: Range<usize>,
+    algorithm: Algorithm,
+    verbose: bool,
+) -> usize {
+    let offsets: Vec<Coordinate<N>> = neighbour_offsets().collect();
+
+    if let Algorithm::MirrorSymmetric = algorithm {
+        assert!(world.is_mirror_symmetric(), "world isn't mirror-symmetric");
+        world.actives.retain(|c| canonical(c) == *c);
+    }
 
-        for (x, y, z, w) in self.actives.clone() {
-            if x < min.0 {
-                min.0 = x;
-            }
-            if y < min.1 {
-                min.1 = y;
-            }
-            if z < min.2 {
-                min.2 = z;
-            }
-            if w < min.3 {
-                min.3 = w;
-            }
-            if x > max.0 {
-                max.0 = x;
-            }
-            if y > max.1 {
-                max.1 = y;
-            }
-            if z > max.2 {
-                max.2 = z;
-            }
-            if w > max.3 {
-                max.3 = w;
-            }
-        }
-        let aabb = Aabb { min, max };
-
-        let mut result = Vec::new();
-        for x in aabb.min.0 - 1..=aabb.max.0 + 1 {
-            for y in aabb.min.1 - 1..=aabb.max.1 + 1 {
-                for z in aabb.min.2 - 1..=aabb.max.2 + 1 {
-                    for w in aabb.min.3 - 1..=aabb.max.3 + 1 {
-                        result.push((x, y, z, w));
-                    }
-                }
+    for i in cycles {
+        *world = match algorithm {
+            Algorithm::BoundingBox => world.step_bounding_box(rule, &offsets),
+            Algorithm::NeighbourTally => world.step_neighbour_tally(rule, &offsets),
+            Algorithm::MirrorSymmetric => world.step_mirror_symmetric(rule, &offsets),
+        };
+
+        if verbose {
+            println!("After {} cycles:\n", i + 1);
+            match algorithm {
+                Algorithm::MirrorSymmetric => print!("{}", world.expanded()),
+                _ => print!("{}", world),
             }
         }
-        result
     }
 
-    fn actives_count(&self) -> usize {
-        self.actives.len()
+    if let Algorithm::MirrorSymmetric = algorithm {
+        *world = world.expanded();
     }
-}
 
-// trait has associated type Coordinate?
+    world.actives_count()
+}
 
-#[derive(Debug, PartialEq, Clone)]
-enum State {
-    Active,
-    Inactive,
+fn benchmark<const N: usize>(content: &str, rule: &Rule) {
+    for algorithm in [
+        Algorithm::BoundingBox,
+        Algorithm::NeighbourTally,
+        Algorithm::MirrorSymmetric,
+    ] {
+        let mut world = World::<N>::parse(content);
+        let start = Instant::now();
+        let result = solve(&mut world, rule, 0..6, algorithm, false);
+        println!(
+            "{}D {:?}: {} in {:?}",
+            N,
+            algorithm,
+            result,
+            start.elapsed()
+        );
+    }
 }
 
-#[derive(Debug)]
-struct Aabb<C> {
-    min: C,
-    max: C,
+struct Options {
+    bench: bool,
+    symmetric: bool,
+    verbose: bool,
+    rule: Rule,
+    cycles: usize,
+    max_dimension: usize,
+    save: Option<PathBuf>,
+    resume: Option<PathBuf>,
 }
 
-impl Aabb<(i64, i64, i64)> {
-    fn from_coordinates(coordinates: &HashSet<(i64, i64, i64)>) -> Self {
-        let mut min = (i64::MAX, i64::MAX, i64::MAX);
-        let mut max = (i64::MIN, i64::MIN, i64::MIN);
+// Usage:
+//   cargo run --release --bin day17 -- [bench] [--symmetric] [--verbose] [--rule <B.../S...>]
+//     [--cycles <n>] [--save <directory>] [--resume <directory>] [<max dimension>]
+//
+// `--save` writes one snapshot per dimension after the last cycle, `--resume` continues from
+// them, e.g. `--cycles 3 --save snapshots` followed by `--resume snapshots`.
+fn parse_args() -> Options {
+    let mut options = Options {
+        bench: false,
+        symmetric: false,
+        verbose: false,
+        rule: CONWAY.parse().unwrap(),
+        cycles: 6,
+        max_dimension: 4,
+        save: None,
+        resume: None,
+    };
 
-        for (x, y, z) in coordinates {
-            if *x < min.0 {
-                min.0 = *x;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "bench" => options.bench = true,
+            "--symmetric" => options.symmetric = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "--rule" => {
+                let rule = args.next().expect("--rule needs a value");
+                options.rule = rule.parse().unwrap_or_else(|e| panic!("{}", e));
             }
-            if *y < min.1 {
-                min.1 = *y;
+            "--cycles" => {
+                options.cycles = args
+                    .next()
+                    .and_then(|c| c.parse().ok())
+                    .expect("--cycles needs a number")
             }
-            if *z < min.2 {
-                min.2 = *z;
-            }
-            if *x > max.0 {
-                max.0 = *x;
-            }
-            if *y > max.1 {
-                max.1 = *y;
-            }
-            if *z > max.2 {
-                max.2 = *z;
+            "--save" => options.save = Some(args.next().expect("--save needs a directory").into()),
+            "--resume" => {
+                options.resume = Some(args.next().expect("--resume needs a directory").into())
             }
+            dimension => options.max_dimension = dimension.parse().expect("max dimension"),
         }
-        Self { min, max }
     }
+
+    options
 }
 
-fn parse_state(c: char) -> State {
-    match c {
-        '.' => State::Inactive,
-        '#' => State::Active,
-        _ => panic!("not a valid character: {}", c),
-    }
+fn snapshot_path<const N: usize>(directory: &Path) -> PathBuf {
+    directory.join(format!("day17_{}d.txt", N))
 }
 
-fn solve<W: World + Clone + fmt::Display>(world: &mut W, max_iterations: usize) -> usize {
-    for _i in 0..max_iterations {
-        println!("{}", world);
-        let old_world = world.clone();
-
-        for c in world.points_to_check() {
-            let active_neighbours = old_world.active_neighbours_count(&c);
-            if old_world.cube_state(&c) == State::Active && !(2..=3).contains(&active_neighbours) {
-                world.remove_active(&c);
-            } else if old_world.cube_state(&c) == State::Inactive && active_neighbours == 3 {
-                world.insert_active(c);
-            }
+/// Runs the simulation for one dimension, starting from the puzzle input or a snapshot.
+fn run<const N: usize>(content: &str, options: &Options, algorithm: Algorithm) -> usize {
+    let (mut world, start) = match &options.resume {
+        Some(directory) => {
+            let snapshot =
+                read_to_string(snapshot_path::<N>(directory)).expect("snapshot not found");
+            World::from_snapshot(&snapshot).unwrap_or_else(|e| panic!("{}", e))
         }
+        None => (World::<N>::parse(content), 0),
+    };
+
+    if options.verbose {
+        println!("{}D, after {} cycles:\n", N, start);
+        print!("{}", world);
     }
 
-    world.actives_count()
-}
+    let result = solve(
+        &mut world,
+        &options.rule,
+        start..options.cycles.max(start),
+        algorithm,
+        options.verbose,
+    );
+
+    if let Some(directory) = &options.save {
+        create_dir_all(directory).expect("can't create snapshot directory");
+        write(
+            snapshot_path::<N>(directory),
+            world.to_snapshot(options.cycles.max(start)),
+        )
+        .expect("can't write snapshot");
+    }
 
-type Coordinate3d = <World3d as World>::Coordinate;
+    result
+}
 
 fn main() {
     let content = read_to_string("./inputs/day17.txt").expect("file not found");
+    let options = parse_args();
+    let algorithm = if options.symmetric {
+        Algorithm::MirrorSymmetric
+    } else {
+        Algorithm::NeighbourTally
+    };
 
-    let world: HashSet<Coordinate3d> = content
-        .lines()
-        .enumerate()
-        .flat_map(|(y, row)| {
-            row.chars()
-                .enumerate()
-                .map(move |(x, c)| ((x as i64, y as i64, 0_i64), parse_state(c)))
-        })
-        .collect::<Vec<_>>()
-        .iter()
-        .filter(|(_c, v)| *v == State::Active)
-        .map(|(c, _v)| c)
-        .copied()
-        .collect();
+    // Compare step algorithms with `bench`.
+    // Release build, 6 iterations:
+    //   3D: bounding box ~9ms, neighbour tally ~1ms, mirror symmetric ~0.7ms
+    //   4D: bounding box ~300ms, neighbour tally ~20ms, mirror symmetric ~7ms
+    //   5D: bounding box ~15s, neighbour tally ~1.6s, mirror symmetric ~100ms
+    if options.bench {
+        benchmark::<3>(&content, &options.rule);
+        benchmark::<4>(&content, &options.rule);
+        benchmark::<5>(&content, &options.rule);
+        return;
+    }
 
     // Part 1
-    let mut w3 = World3d {
-        actives: world.clone(),
-    };
     // Result: 209
-    println!("{}", solve(&mut w3, 6));
+    println!("{}", run::<3>(&content, &options, algorithm));
 
     // Part 2
-    let mut w4 = World4d {
-        actives: world.iter().map(|(x, y, z)| (*x, *y, *z, 0_i64)).collect(),
-    };
     // Result: 1492
-    println!("{}", solve(&mut w4, 6));
+    println!("{}", run::<4>(&content, &options, algorithm));
+
+    // Higher dimensions are opt-in.
+    if options.max_dimension >= 5 {
+        println!("5D: {}", run::<5>(&content, &options, algorithm));
+    }
+    if options.max_dimension >= 6 {
+        println!("6D: {}", run::<6>(&content, &options, algorithm));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = ".#.\n..#\n###";
+
+    #[test]
+    fn test_neighbour_offsets() {
+        assert_eq!(neighbour_offsets::<3>().count(), 26);
+        assert_eq!(neighbour_offsets::<4>().count(), 80);
+    }
+
+    #[test]
+    fn test_example() {
+        for algorithm in [
+            Algorithm::BoundingBox,
+            Algorithm::NeighbourTally,
+            Algorithm::MirrorSymmetric,
+        ] {
+            let rule = CONWAY.parse().unwrap();
+            assert_eq!(
+                solve(
+                    &mut World::<3>::parse(EXAMPLE),
+                    &rule,
+                    0..6,
+                    algorithm,
+                    false
+                ),
+                112
+            );
+            assert_eq!(
+                solve(
+                    &mut World::<4>::parse(EXAMPLE),
+                    &rule,
+                    0..6,
+                    algorithm,
+                    false
+                ),
+                848
+            );
+        }
+    }
+
+    #[test]
+    fn test_algorithms_agree_on_other_rules() {
+        for rule in ["B36/S23", "B2/S", "B3/S012345678"] {
+            let rule: Rule = rule.parse().unwrap();
+            let mut a = World::<4>::parse(EXAMPLE);
+            let mut b = World::<4>::parse(EXAMPLE);
+            let mut c = World::<4>::parse(EXAMPLE);
+            let expected = solve(&mut a, &rule, 0..4, Algorithm::BoundingBox, false);
+            assert_eq!(
+                solve(&mut b, &rule, 0..4, Algorithm::NeighbourTally, false),
+                expected
+            );
+            assert_eq!(
+                solve(&mut c, &rule, 0..4, Algorithm::MirrorSymmetric, false),
+                expected
+            );
+            assert_eq!(a.actives, c.actives);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let world = World::<4>::parse(EXAMPLE);
+        assert_eq!(world.to_string(), "z=0, w=0\n.#.\n..#\n###\n\n");
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let rule = CONWAY.parse().unwrap();
+        let mut world = World::<4>::parse(EXAMPLE);
+        solve(&mut world, &rule, 0..3, Algorithm::NeighbourTally, false);
+
+        let (mut resumed, cycle) = World::<4>::from_snapshot(&world.to_snapshot(3)).unwrap();
+        assert_eq!(cycle, 3);
+        assert_eq!(resumed.actives, world.actives);
+        assert_eq!(
+            solve(&mut resumed, &rule, 3..6, Algorithm::NeighbourTally, false),
+            848
+        );
+
+        assert!(World::<3>::from_snapshot(&world.to_snapshot(3)).is_err());
+        assert!(World::<4>::from_snapshot("day17 4 0\n1,2,3").is_err());
+    }
 }
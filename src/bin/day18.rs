@@ -1,10 +1,19 @@
+use num_bigint::{BigInt, Sign};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs::read_to_string;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
 use std::rc::Rc;
 
 #[derive(Debug, PartialEq, Clone)]
 enum Expression {
     Binary(Operator, Rc<Expression>, Rc<Expression>),
-    Literal(i64),
+    Negate(Rc<Expression>),
+    Literal(BigInt),
+    /// A `let`-bound name or `$N`, the N-th result in the REPL.
+    Variable(String),
     Grouped(Rc<Expression>),
 }
 
@@ -12,157 +21,840 @@ enum Expression {
 enum Operator {
     Mul,
     Add,
+    Sub,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl Operator {
+    fn symbol(&self) -> char {
+        match self {
+            Operator::Mul => '*',
+            Operator::Add => '+',
+            Operator::Sub => '-',
+            Operator::Div => '/',
+            Operator::Rem => '%',
+            Operator::Pow => '^',
+        }
+    }
 }
 
 use Expression::*;
 
 #[derive(Debug, PartialEq)]
-enum ParseError {
-    GenericParseError(String),
+struct ParseError {
+    message: String,
+    /// Byte offsets into the source.
+    span: Range<usize>,
 }
 
-fn try_parse_literal(lit: &str, part_1: bool) -> Result<(Expression, &str), ParseError> {
-    match lit.chars().nth(0).unwrap() {
-        '(' => {
-            let (expr, rest) = if part_1 {
-                parse_inner_expression(&lit[1..])?
-            } else {
-                try_parse_product(&lit[1..])?
-            };
-            if rest.chars().nth(0) != Some(')') {
-                return Err(ParseError::GenericParseError(
-                    "missing closing )".to_owned(),
-                ));
-            }
-            return Ok((Grouped(Rc::new(expr)), &rest[1..]));
+impl ParseError {
+    fn new(message: &str, span: Range<usize>) -> Self {
+        ParseError {
+            message: message.to_owned(),
+            span,
         }
-        '0'..='9' => {
-            let digit_chars = lit.chars().take_while(|c| c.is_digit(10));
-            return Ok((
-                Literal(digit_chars.clone().collect::<String>().parse().unwrap()),
-                &lit[digit_chars.count()..],
-            ));
+    }
+
+    /// Shows the offending source line with carets under the error's span.
+    fn render(&self, source: &str) -> String {
+        let line_start = source[..self.span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |i| line_start + i);
+        let column = source[line_start..self.span.start].chars().count();
+        let width = source[self.span.start..self.span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        format!(
+            "{}\n{}{} {}",
+            &source[line_start..line_end],
+            " ".repeat(column),
+            "^".repeat(width),
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum TokenKind {
+    Number(BigInt),
+    Identifier(String),
+    Operator(Operator),
+    LeftParen,
+    RightParen,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '+' => TokenKind::Operator(Operator::Add),
+            '-' => TokenKind::Operator(Operator::Sub),
+            '*' => TokenKind::Operator(Operator::Mul),
+            '/' => TokenKind::Operator(Operator::Div),
+            '%' => TokenKind::Operator(Operator::Rem),
+            '^' => TokenKind::Operator(Operator::Pow),
+            '0'..='9' => {
+                while chars.next_if(|(_, c)| c.is_ascii_digit()).is_some() {}
+                let end = chars.peek().map_or(source.len(), |(i, _)| *i);
+                let number = source[start..end].parse().expect("only digits");
+                TokenKind::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while chars
+                    .next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+                    .is_some()
+                {}
+                let end = chars.peek().map_or(source.len(), |(i, _)| *i);
+                TokenKind::Identifier(source[start..end].to_owned())
+            }
+            '$' => {
+                while chars.next_if(|(_, c)| c.is_ascii_digit()).is_some() {}
+                let end = chars.peek().map_or(source.len(), |(i, _)| *i);
+                if end == start + 1 {
+                    return Err(ParseError::new(
+                        "expected result number after $",
+                        start..end,
+                    ));
+                }
+                TokenKind::Identifier(source[start..end].to_owned())
+            }
+            c => {
+                return Err(ParseError::new(
+                    &format!("unexpected character {:?}", c),
+                    start..start + c.len_utf8(),
+                ))
+            }
+        };
+        // Nothing after the token has been consumed yet.
+        let end = chars.peek().map_or(source.len(), |(i, _)| *i);
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, PartialEq)]
+enum EvalError {
+    DivisionByZero,
+    NegativeExponent,
+    UnboundVariable(String),
+    /// The result of this subexpression doesn't fit into the chosen number type, or for `BigInt`
+    /// into `BIGINT_MAX_BITS`.
+    Overflow(Expression),
+}
+
+impl EvalError {
+    /// Shows an overflowing subexpression the way it'd be written under `table`.
+    fn display<'a>(&'a self, table: &'a OperatorTable) -> EvalErrorDisplay<'a> {
+        EvalErrorDisplay { error: self, table }
+    }
+}
+
+struct EvalErrorDisplay<'a> {
+    error: &'a EvalError,
+    table: &'a OperatorTable,
+}
+
+impl fmt::Display for EvalErrorDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::NegativeExponent => write!(f, "negative exponent"),
+            EvalError::UnboundVariable(name) => write!(f, "unknown variable {}", name),
+            EvalError::Overflow(expr) => {
+                write!(f, "overflow in {}", pretty_print(expr, self.table))
+            }
         }
-        _ => Err(ParseError::GenericParseError(
-            "neither number nor (".to_owned(),
-        )),
     }
 }
 
-fn try_parse_operator(
-    op: &str,
-    c: char,
-    operator: Operator,
-) -> Result<(Operator, &str), ParseError> {
-    if op.chars().nth(0) == Some(c) {
-        Ok((operator, &op[1..]))
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display(&STANDARD).fmt(f)
+    }
+}
+
+/// The numbers `evaluate` can work with. Conversions and operations return `None` if the result
+/// doesn't fit.
+trait Number: Sized + Clone + fmt::Display {
+    fn zero() -> Self;
+    fn from_literal(literal: &BigInt) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+    fn is_negative(&self) -> bool;
+    fn checked_negate(&self) -> Option<Self>;
+    /// Division by zero and negative exponents are ruled out by the caller.
+    fn checked_binary(&self, operator: &Operator, other: &Self) -> Option<Self>;
+}
+
+macro_rules! impl_number_for_primitive {
+    ($t:ty) => {
+        impl Number for $t {
+            fn zero() -> Self {
+                0
+            }
+
+            fn from_literal(literal: &BigInt) -> Option<Self> {
+                literal.try_into().ok()
+            }
+
+            fn is_zero(&self) -> bool {
+                *self == 0
+            }
+
+            fn is_negative(&self) -> bool {
+                *self < 0
+            }
+
+            fn checked_negate(&self) -> Option<Self> {
+                self.checked_neg()
+            }
+
+            fn checked_binary(&self, operator: &Operator, other: &Self) -> Option<Self> {
+                match operator {
+                    Operator::Add => self.checked_add(*other),
+                    Operator::Sub => self.checked_sub(*other),
+                    Operator::Mul => self.checked_mul(*other),
+                    Operator::Div => self.checked_div(*other),
+                    Operator::Rem => self.checked_rem(*other),
+                    Operator::Pow => match u32::try_from(*other) {
+                        Ok(exponent) => self.checked_pow(exponent),
+                        // Only 0, 1 and -1 stay small for such exponents.
+                        Err(_) => match *self {
+                            0 | 1 => Some(*self),
+                            -1 if *other % 2 == 0 => Some(1),
+                            -1 => Some(-1),
+                            _ => None,
+                        },
+                    },
+                }
+            }
+        }
+    };
+}
+
+impl_number_for_primitive!(i64);
+impl_number_for_primitive!(i128);
+
+/// Results with more bits are treated as overflow, so that e.g. `2^4000000000` fails quickly
+/// instead of allocating gigabytes.
+const BIGINT_MAX_BITS: u64 = 1 << 20;
+
+fn capped(n: BigInt) -> Option<BigInt> {
+    if n.bits() <= BIGINT_MAX_BITS {
+        Some(n)
     } else {
-        Err(ParseError::GenericParseError(format!("no {}", c)))
+        None
+    }
+}
+
+impl Number for BigInt {
+    fn zero() -> Self {
+        BigInt::from(0)
+    }
+
+    fn from_literal(literal: &BigInt) -> Option<Self> {
+        capped(literal.clone())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.sign() == Sign::NoSign
     }
+
+    fn is_negative(&self) -> bool {
+        self.sign() == Sign::Minus
+    }
+
+    fn checked_negate(&self) -> Option<Self> {
+        Some(-self)
+    }
+
+    fn checked_binary(&self, operator: &Operator, other: &Self) -> Option<Self> {
+        match operator {
+            Operator::Add => capped(self + other),
+            Operator::Sub => capped(self - other),
+            Operator::Mul => capped(self * other),
+            Operator::Div => Some(self / other),
+            Operator::Rem => Some(self % other),
+            Operator::Pow if other.is_zero() => Some(BigInt::from(1)),
+            // 0, 1 and -1 keep their magnitude for any exponent.
+            Operator::Pow if self.bits() <= 1 => {
+                if self.is_negative() && !other.bit(0) {
+                    Some(-self)
+                } else {
+                    Some(self.clone())
+                }
+            }
+            Operator::Pow => {
+                // The result has more than `exponent * (bits - 1)` bits, check before computing it.
+                let exponent = u64::try_from(other).ok()?;
+                if (self.bits() - 1).saturating_mul(exponent) > BIGINT_MAX_BITS {
+                    return None;
+                }
+                capped(self.pow(exponent as u32))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+/// Binding power and associativity of every binary operator, as well as the binding power of
+/// unary minus. Operators with a higher precedence bind tighter.
+struct OperatorTable {
+    binary: &'static [(Operator, u8, Associativity)],
+    negate: u8,
 }
 
-fn try_parse_some_operator(op: &str) -> Result<(Operator, &str), ParseError> {
-    return try_parse_operator(&op, '*', Operator::Mul)
-        .or(try_parse_operator(&op, '+', Operator::Add))
-        .map_err(|_| ParseError::GenericParseError("neither + nor *".to_owned()));
+impl OperatorTable {
+    fn lookup(&self, operator: &Operator) -> Option<(u8, Associativity)> {
+        self.binary
+            .iter()
+            .find(|(o, _, _)| o == operator)
+            .map(|(_, precedence, associativity)| (*precedence, *associativity))
+    }
+}
+
+/// Part 1: evaluated from left to right. Only `^` binds tighter and, as in every mode, groups
+/// from the right. Unary minus binds tightest, so `-2^2` is `(-2)^2`.
+const FLAT: OperatorTable = OperatorTable {
+    binary: &[
+        (Operator::Add, 1, Associativity::Left),
+        (Operator::Sub, 1, Associativity::Left),
+        (Operator::Mul, 1, Associativity::Left),
+        (Operator::Div, 1, Associativity::Left),
+        (Operator::Rem, 1, Associativity::Left),
+        (Operator::Pow, 2, Associativity::Right),
+    ],
+    negate: 3,
+};
+
+/// Part 2: addition binds tighter than multiplication.
+const ADD_FIRST: OperatorTable = OperatorTable {
+    binary: &[
+        (Operator::Mul, 1, Associativity::Left),
+        (Operator::Div, 1, Associativity::Left),
+        (Operator::Rem, 1, Associativity::Left),
+        (Operator::Add, 2, Associativity::Left),
+        (Operator::Sub, 2, Associativity::Left),
+        (Operator::Pow, 4, Associativity::Right),
+    ],
+    negate: 3,
+};
+
+/// The usual precedence rules, `-2^2` is `-(2^2)`.
+const STANDARD: OperatorTable = OperatorTable {
+    binary: &[
+        (Operator::Add, 1, Associativity::Left),
+        (Operator::Sub, 1, Associativity::Left),
+        (Operator::Mul, 2, Associativity::Left),
+        (Operator::Div, 2, Associativity::Left),
+        (Operator::Rem, 2, Associativity::Left),
+        (Operator::Pow, 4, Associativity::Right),
+    ],
+    negate: 3,
+};
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    table: &'a OperatorTable,
+    source_len: usize,
 }
 
-fn try_parse_product(expr: &str) -> Result<(Expression, &str), ParseError> {
-    let (lit1, rest) = try_parse_sum(&expr)?;
-    let mut rest_expr = vec![];
-    let mut rest = rest;
-    loop {
-        if let Ok((op, after_operator)) = try_parse_operator(&rest, '*', Operator::Mul) {
-            let (lit2, after_literal) = try_parse_sum(&after_operator)?;
-            rest_expr.push((op, lit2));
-            rest = after_literal;
-        } else {
-            break;
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// The span of the next token, or an empty span at the end of the source.
+    fn next_span(&self) -> Range<usize> {
+        self.peek()
+            .map_or(self.source_len..self.source_len, |t| t.span.clone())
+    }
+
+    fn parse_literal(&mut self) -> Result<Expression, ParseError> {
+        let token = self
+            .peek()
+            .ok_or_else(|| ParseError::new("expected number, - or (", self.next_span()))?;
+        self.position += 1;
+
+        match &token.kind {
+            TokenKind::LeftParen => {
+                let expr = self.parse_binary(0)?;
+                match self.peek() {
+                    Some(Token {
+                        kind: TokenKind::RightParen,
+                        ..
+                    }) => {
+                        self.position += 1;
+                        Ok(Grouped(Rc::new(expr)))
+                    }
+                    _ => Err(ParseError::new("missing closing )", self.next_span())),
+                }
+            }
+            TokenKind::Operator(Operator::Sub) => {
+                let expr = self.parse_binary(self.table.negate)?;
+                Ok(Negate(Rc::new(expr)))
+            }
+            TokenKind::Number(n) => Ok(Literal(n.clone())),
+            TokenKind::Identifier(name) => Ok(Variable(name.clone())),
+            _ => Err(ParseError::new(
+                "expected number, - or (",
+                token.span.clone(),
+            )),
         }
     }
-    if rest_expr.is_empty() {
-        return Ok((lit1, rest));
+
+    /// Precedence climbing: parses operands and every following operator binding at least as
+    /// tight as `min_precedence`.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, ParseError> {
+        let mut left = self.parse_literal()?;
+
+        while let Some(Token {
+            kind: TokenKind::Operator(op),
+            span,
+        }) = self.peek()
+        {
+            let (precedence, associativity) = self.table.lookup(op).ok_or_else(|| {
+                ParseError::new("operator isn't part of the operator table", span.clone())
+            })?;
+            if precedence < min_precedence {
+                break;
+            }
+            self.position += 1;
+            let next_min_precedence = match associativity {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            };
+            let right = self.parse_binary(next_min_precedence)?;
+            left = Binary(op.clone(), Rc::new(left), Rc::new(right));
+        }
+
+        Ok(left)
     }
-    let mut left_bottom_tree = Binary(
-        rest_expr[0].0.clone(),
-        Rc::new(lit1),
-        Rc::new(rest_expr[0].1.clone()),
-    );
+}
 
-    // TODO: Use detain from vec to move out of vec without `clone`.
-    for (op, exp) in &rest_expr[1..] {
-        left_bottom_tree = Binary(op.clone(), Rc::new(left_bottom_tree), Rc::new(exp.clone()));
+fn parse_expression(source: &str, table: &OperatorTable) -> Result<Expression, ParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        table,
+        source_len: source.len(),
+    };
+    let expr = parser.parse_binary(0)?;
+
+    match parser.peek() {
+        None => Ok(expr),
+        Some(Token {
+            kind: TokenKind::RightParen,
+            span,
+        }) => Err(ParseError::new("unmatched )", span.clone())),
+        Some(token) => Err(ParseError::new(
+            "expected operator or end of input",
+            token.span.clone(),
+        )),
     }
+}
+
+fn parse_expression_part_1(expr: &str) -> Result<Expression, ParseError> {
+    parse_expression(expr, &FLAT)
+}
 
-    Ok((left_bottom_tree, rest))
+fn parse_expression_part_2(expr: &str) -> Result<Expression, ParseError> {
+    parse_expression(expr, &ADD_FIRST)
 }
 
-fn try_parse_sum(expr: &str) -> Result<(Expression, &str), ParseError> {
-    let (lit1, rest) = try_parse_literal(&expr, false)?;
-    let mut rest_expr = vec![];
-    let mut rest = rest;
-    loop {
-        if let Ok((op, after_operator)) = try_parse_operator(&rest, '+', Operator::Add) {
-            let (lit2, after_literal) = try_parse_literal(&after_operator, false)?;
-            rest_expr.push((op, lit2));
-            rest = after_literal;
-        } else {
-            break;
+/// How tightly `expr` holds together as an operand under `table`. Groupings are looked through,
+/// the printer decides on its own where parentheses are needed.
+fn binding_power(expr: &Expression, table: &OperatorTable) -> u8 {
+    match expr {
+        Binary(op, _, _) => {
+            table
+                .lookup(op)
+                .expect("operator isn't part of the operator table")
+                .0
         }
+        Negate(_) => table.negate,
+        Literal(_) | Variable(_) => u8::MAX,
+        Grouped(inner) => binding_power(inner, table),
     }
-    if rest_expr.is_empty() {
-        return Ok((lit1, rest));
+}
+
+fn write_operand(expr: &Expression, table: &OperatorTable, parenthesize: bool, out: &mut String) {
+    if parenthesize {
+        out.push('(');
+        write_expression(expr, table, out);
+        out.push(')');
+    } else {
+        write_expression(expr, table, out);
     }
-    let mut left_bottom_tree = Binary(
-        rest_expr[0].0.clone(),
-        Rc::new(lit1),
-        Rc::new(rest_expr[0].1.clone()),
-    );
+}
+
+fn write_expression(expr: &Expression, table: &OperatorTable, out: &mut String) {
+    match expr {
+        Grouped(inner) => write_expression(inner, table, out),
+        Literal(n) => out.push_str(&n.to_string()),
+        Variable(name) => out.push_str(name),
+        Negate(inner) => {
+            out.push('-');
+            let parenthesize = binding_power(inner, table) < table.negate;
+            write_operand(inner, table, parenthesize, out);
+        }
+        Binary(op, left, right) => {
+            let (precedence, associativity) = table
+                .lookup(op)
+                .expect("operator isn't part of the operator table");
+            let left_power = binding_power(left, table);
+            let right_power = binding_power(right, table);
+
+            write_operand(
+                left,
+                table,
+                left_power < precedence
+                    || (left_power == precedence && associativity == Associativity::Right),
+                out,
+            );
+            out.push(' ');
+            out.push(op.symbol());
+            out.push(' ');
+            write_operand(
+                right,
+                table,
+                right_power < precedence
+                    || (right_power == precedence && associativity == Associativity::Left),
+                out,
+            );
+        }
+    }
+}
+
+/// Prints `expr` with as few parentheses as possible, such that parsing the result with `table`
+/// gives back the same tree (up to groupings).
+fn pretty_print(expr: &Expression, table: &OperatorTable) -> String {
+    let mut out = String::new();
+    write_expression(expr, table, &mut out);
+    out
+}
+
+/// Rewrites `source`, written for the `from` rules, into an expression which evaluates the same
+/// under the `to` rules.
+fn rewrite(source: &str, from: &OperatorTable, to: &OperatorTable) -> Result<String, ParseError> {
+    Ok(pretty_print(&parse_expression(source, from)?, to))
+}
+
+type Variables<N> = HashMap<String, N>;
+
+/// Division and remainder truncate towards zero, like Rust's `/` and `%`.
+fn evaluate_in<N: Number>(expr: &Expression, variables: &Variables<N>) -> Result<N, EvalError> {
+    let overflow = || EvalError::Overflow(expr.clone());
 
-    // TODO: Use detain from vec to move out of vec without `clone`.
-    for (op, exp) in &rest_expr[1..] {
-        left_bottom_tree = Binary(op.clone(), Rc::new(left_bottom_tree), Rc::new(exp.clone()));
+    match expr {
+        Grouped(expr) => evaluate_in(expr, variables),
+        Negate(inner) => evaluate_in(inner, variables)?
+            .checked_negate()
+            .ok_or_else(overflow),
+        Binary(op, e1, e2) => {
+            let left: N = evaluate_in(e1, variables)?;
+            let right: N = evaluate_in(e2, variables)?;
+            match op {
+                Operator::Div | Operator::Rem if right.is_zero() => Err(EvalError::DivisionByZero),
+                Operator::Pow if right.is_negative() => Err(EvalError::NegativeExponent),
+                _ => left.checked_binary(op, &right).ok_or_else(overflow),
+            }
+        }
+        Literal(l) => N::from_literal(l).ok_or_else(overflow),
+        Variable(name) => variables
+            .get(name)
+            .cloned()
+            .ok_or_else(|| EvalError::UnboundVariable(name.clone())),
     }
+}
 
-    Ok((left_bottom_tree, rest))
+fn evaluate<N: Number>(expr: &Expression) -> Result<N, EvalError> {
+    evaluate_in(expr, &Variables::new())
 }
 
-fn parse_inner_expression(expr: &str) -> Result<(Expression, &str), ParseError> {
-    let (lit1, rest) = try_parse_literal(&expr, true)?;
-    let mut rest_expr = vec![];
-    let mut rest = rest;
-    loop {
-        if let Ok((op, after_operator)) = try_parse_some_operator(&rest) {
-            let (lit2, after_literal) = try_parse_literal(&after_operator, true)?;
-            rest_expr.push((op, lit2));
-            rest = after_literal;
-        } else {
-            break;
+/// Stack machine code: operands are pushed, operators pop theirs and push the result.
+#[derive(Debug, PartialEq, Clone)]
+enum Instruction {
+    Push(BigInt),
+    Load(String),
+    Negate,
+    Binary(Operator),
+}
+
+#[derive(Debug, PartialEq)]
+enum VmError {
+    DivisionByZero,
+    NegativeExponent,
+    UnboundVariable(String),
+    /// The instruction at this offset overflowed.
+    Overflow(usize),
+    /// The code pops more values than it pushed or leaves more than one behind.
+    MalformedCode,
+}
+
+fn compile_into(expr: &Expression, code: &mut Vec<Instruction>) {
+    match expr {
+        Grouped(inner) => compile_into(inner, code),
+        Literal(n) => code.push(Instruction::Push(n.clone())),
+        Variable(name) => code.push(Instruction::Load(name.clone())),
+        Negate(inner) => {
+            compile_into(inner, code);
+            code.push(Instruction::Negate);
         }
+        Binary(op, left, right) => {
+            compile_into(left, code);
+            compile_into(right, code);
+            code.push(Instruction::Binary(op.clone()));
+        }
+    }
+}
+
+fn compile(expr: &Expression) -> Vec<Instruction> {
+    let mut code = vec![];
+    compile_into(expr, &mut code);
+    code
+}
+
+fn run_code_in<N: Number>(code: &[Instruction], variables: &Variables<N>) -> Result<N, VmError> {
+    let mut stack: Vec<N> = vec![];
+
+    for (offset, instruction) in code.iter().enumerate() {
+        let result = match instruction {
+            Instruction::Push(n) => N::from_literal(n),
+            Instruction::Load(name) => Some(
+                variables
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| VmError::UnboundVariable(name.clone()))?,
+            ),
+            Instruction::Negate => stack.pop().ok_or(VmError::MalformedCode)?.checked_negate(),
+            Instruction::Binary(op) => {
+                let right = stack.pop().ok_or(VmError::MalformedCode)?;
+                let left = stack.pop().ok_or(VmError::MalformedCode)?;
+                match op {
+                    Operator::Div | Operator::Rem if right.is_zero() => {
+                        return Err(VmError::DivisionByZero)
+                    }
+                    Operator::Pow if right.is_negative() => return Err(VmError::NegativeExponent),
+                    _ => left.checked_binary(op, &right),
+                }
+            }
+        };
+        stack.push(result.ok_or(VmError::Overflow(offset))?);
+    }
+
+    match (stack.pop(), stack.is_empty()) {
+        (Some(result), true) => Ok(result),
+        _ => Err(VmError::MalformedCode),
     }
-    if rest_expr.is_empty() {
-        return Ok((lit1, rest));
+}
+
+fn run_code<N: Number>(code: &[Instruction]) -> Result<N, VmError> {
+    run_code_in(code, &Variables::new())
+}
+
+fn disassemble(code: &[Instruction]) -> String {
+    code.iter()
+        .enumerate()
+        .map(|(offset, instruction)| match instruction {
+            Instruction::Push(n) => format!("{:04} push {}\n", offset, n),
+            Instruction::Load(name) => format!("{:04} load {}\n", offset, name),
+            Instruction::Negate => format!("{:04} neg\n", offset),
+            Instruction::Binary(op) => {
+                let mnemonic = match op {
+                    Operator::Mul => "mul",
+                    Operator::Add => "add",
+                    Operator::Sub => "sub",
+                    Operator::Div => "div",
+                    Operator::Rem => "rem",
+                    Operator::Pow => "pow",
+                };
+                format!("{:04} {}\n", offset, mnemonic)
+            }
+        })
+        .collect()
+}
+
+/// Runs every line through both the tree walker and the bytecode VM and panics if they disagree.
+fn check_vm<N: Number + PartialEq + fmt::Debug>(content: &str, table: &OperatorTable) {
+    for line in content.lines() {
+        let expr = parse_expression(line, table).unwrap_or_else(|e| panic!("\n{}", e.render(line)));
+        let expected = evaluate::<N>(&expr).ok();
+        assert_eq!(run_code::<N>(&compile(&expr)).ok(), expected, "{}", line);
     }
-    let mut left_bottom_tree = Binary(
-        rest_expr[0].0.clone(),
-        Rc::new(lit1),
-        Rc::new(rest_expr[0].1.clone()),
+}
+
+fn evaluate_all<N: Number>(
+    content: &str,
+    parse: impl Fn(&str) -> Result<Expression, ParseError>,
+) -> N {
+    content
+        .lines()
+        .map(|l| parse(l).unwrap_or_else(|e| panic!("\n{}", e.render(l))))
+        .map(|e| evaluate::<N>(&e).unwrap_or_else(|e| panic!("{}", e)))
+        .try_fold(N::zero(), |sum, n| sum.checked_binary(&Operator::Add, &n))
+        .expect("sum overflows")
+}
+
+fn print_results<N: Number + PartialEq + fmt::Debug>(content: &str) {
+    for table in [&FLAT, &ADD_FIRST, &STANDARD] {
+        check_vm::<N>(content, table);
+    }
+
+    // Part 1
+    // Result: 98621258158412
+    println!("{}", evaluate_all::<N>(content, parse_expression_part_1));
+
+    // Part 2
+    // Result: 241216538527890
+    println!("{}", evaluate_all::<N>(content, parse_expression_part_2));
+
+    // For comparison, with the usual precedence rules.
+    println!(
+        "{}",
+        evaluate_all::<N>(content, |l| parse_expression(l, &STANDARD))
     );
+}
+
+/// Shows what the first few homework lines mean in ordinary notation.
+fn explain(content: &str) {
+    for line in content.lines().take(5) {
+        println!("{}", line);
+        for (name, table) in [("part 1", &FLAT), ("part 2", &ADD_FIRST)] {
+            let rewritten =
+                rewrite(line, table, &STANDARD).unwrap_or_else(|e| panic!("\n{}", e.render(line)));
+            println!("  {}: {}", name, rewritten);
+        }
+    }
+}
 
-    // TODO: Use detain from vec to move out of vec without `clone`.
-    for (op, exp) in &rest_expr[1..] {
-        left_bottom_tree = Binary(op.clone(), Rc::new(left_bottom_tree), Rc::new(exp.clone()));
+/// Calculator state: results are kept as `$1`, `$2`, ... next to the `let` bindings.
+struct Repl {
+    table: &'static OperatorTable,
+    variables: Variables<BigInt>,
+    results: usize,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl Repl {
+    fn new() -> Self {
+        Repl {
+            table: &STANDARD,
+            variables: Variables::new(),
+            results: 0,
+        }
     }
 
-    Ok((left_bottom_tree, rest))
+    /// Handles one input line and returns what to print.
+    fn handle(&mut self, line: &str) -> String {
+        let line = line.trim();
+
+        if let Some(mode) = line.strip_prefix(":mode") {
+            self.table = match mode.trim() {
+                "flat" => &FLAT,
+                "add-first" => &ADD_FIRST,
+                "standard" => &STANDARD,
+                other => {
+                    return format!("unknown mode {:?}, try flat, add-first or standard", other)
+                }
+            };
+            return format!("mode {}", mode.trim());
+        }
+
+        let (name, source) = match line.strip_prefix("let ").and_then(|l| l.split_once('=')) {
+            Some((name, source)) if is_identifier(name.trim()) => (Some(name.trim()), source),
+            Some((name, _)) => return format!("invalid variable name {:?}", name.trim()),
+            None => (None, line),
+        };
+
+        let expr = match parse_expression(source, self.table) {
+            Ok(expr) => expr,
+            Err(e) => return e.render(source),
+        };
+        let value: BigInt = match evaluate_in(&expr, &self.variables) {
+            Ok(value) => value,
+            Err(e) => return e.display(self.table).to_string(),
+        };
+
+        self.results += 1;
+        let result = format!("${}", self.results);
+        self.variables.insert(result.clone(), value.clone());
+        match name {
+            Some(name) => {
+                self.variables.insert(name.to_owned(), value.clone());
+                format!("{} = {} = {}", result, name, value)
+            }
+            None => format!("{} = {}", result, value),
+        }
+    }
 }
 
-fn parse_expression_part_1(expr: &str) -> Result<Expression, ParseError> {
-    parse_inner_expression(&expr).map(|(e, _r)| e)
+fn repl() {
+    let mut repl = Repl::new();
+    let stdin = io::stdin();
+
+    print!("> ");
+    io::stdout().flush().expect("stdout closed");
+    for line in stdin.lock().lines() {
+        let line = line.expect("can't read stdin");
+        if !line.trim().is_empty() {
+            println!("{}", repl.handle(&line));
+        }
+        print!("> ");
+        io::stdout().flush().expect("stdout closed");
+    }
+    println!();
 }
 
-fn parse_expression_part_2(expr: &str) -> Result<Expression, ParseError> {
-    try_parse_product(&expr).map(|(e, _r)| e)
+fn main() {
+    let content = read_to_string("./inputs/day18.txt").expect("file not found");
+
+    // Usage: cargo run --bin day18 -- [i64|i128|big|explain|disassemble|repl]
+    match env::args().nth(1).as_deref() {
+        Some("repl") => repl(),
+        Some("explain") => explain(&content),
+        Some("disassemble") => {
+            let line = content.lines().next().expect("empty input");
+            let expr =
+                parse_expression_part_2(line).unwrap_or_else(|e| panic!("\n{}", e.render(line)));
+            println!("{}\n{}", line, disassemble(&compile(&expr)));
+        }
+        None | Some("i64") => print_results::<i64>(&content),
+        Some("i128") => print_results::<i128>(&content),
+        Some("big") => print_results::<BigInt>(&content),
+        Some(other) => panic!("unknown number type {}", other),
+    }
 }
 
 #[cfg(test)]
@@ -177,10 +869,10 @@ mod tests {
                 Operator::Mul,
                 Rc::new(Expression::Grouped(Rc::new(Expression::Binary(
                     Operator::Add,
-                    Rc::new(Expression::Literal(2)),
-                    Rc::new(Expression::Literal(3))
+                    Rc::new(Expression::Literal(BigInt::from(2))),
+                    Rc::new(Expression::Literal(BigInt::from(3)))
                 )))),
-                Rc::new(Expression::Literal(4))
+                Rc::new(Expression::Literal(BigInt::from(4)))
             ))))
         );
         assert_eq!(
@@ -189,10 +881,10 @@ mod tests {
                 Operator::Mul,
                 Rc::new(Expression::Binary(
                     Operator::Add,
-                    Rc::new(Expression::Literal(2)),
-                    Rc::new(Expression::Literal(3))
+                    Rc::new(Expression::Literal(BigInt::from(2))),
+                    Rc::new(Expression::Literal(BigInt::from(3)))
                 )),
-                Rc::new(Expression::Literal(4)),
+                Rc::new(Expression::Literal(BigInt::from(4))),
             ))
         );
 
@@ -202,14 +894,14 @@ mod tests {
                 Operator::Add,
                 Rc::new(Expression::Binary(
                     Operator::Add,
-                    Rc::new(Expression::Literal(2)),
+                    Rc::new(Expression::Literal(BigInt::from(2))),
                     Rc::new(Expression::Grouped(Rc::new(Expression::Binary(
                         Operator::Mul,
-                        Rc::new(Expression::Literal(3)),
-                        Rc::new(Expression::Literal(4))
+                        Rc::new(Expression::Literal(BigInt::from(3))),
+                        Rc::new(Expression::Literal(BigInt::from(4)))
                     ))))
                 )),
-                Rc::new(Expression::Literal(3)),
+                Rc::new(Expression::Literal(BigInt::from(3))),
             ))
         );
     }
@@ -220,66 +912,422 @@ mod tests {
             parse_expression_part_2("2*3+4"),
             Ok(Expression::Binary(
                 Operator::Mul,
-                Rc::new(Expression::Literal(2)),
+                Rc::new(Expression::Literal(BigInt::from(2))),
                 Rc::new(Expression::Binary(
                     Operator::Add,
-                    Rc::new(Expression::Literal(3)),
-                    Rc::new(Expression::Literal(4))
+                    Rc::new(Expression::Literal(BigInt::from(3))),
+                    Rc::new(Expression::Literal(BigInt::from(4)))
                 ))
             ))
         );
     }
 
     #[test]
-    fn examples() {
-        let exp = parse_expression_part_2("1+(2*3)+(4*(5+6))").expect("correct parsing");
-        assert_eq!(interpret(&exp), 51);
+    fn test_right_associativity() {
+        let exp = parse_expression("2^3^2", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(512));
 
-        let exp = parse_expression_part_2("2*3+(4*5)").expect("correct parsing");
-        assert_eq!(interpret(&exp), 46);
+        let exp = parse_expression("2^3^2", &FLAT).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(512));
 
-        let exp = parse_expression_part_2("((2+4*9)*(6+9*8+6)+6)+2+4*2").expect("correct parsing");
-        assert_eq!(interpret(&exp), 23340);
+        let exp = parse_expression("1+2*3^2", &FLAT).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(27));
+
+        let exp = parse_expression("2^3*2", &FLAT).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(16));
     }
-}
 
-fn remove_whitespace(str: &str) -> String {
-    str.chars().filter(|c| !c.is_whitespace()).collect()
-}
+    #[test]
+    fn test_subtraction_division_remainder() {
+        let exp = parse_expression("10-4-3", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(3));
 
-fn interpret(expr: &Expression) -> i64 {
-    match expr {
-        Grouped(expr) => interpret(expr),
-        Binary(Operator::Mul, e1, e2) => interpret(e1) * interpret(e2),
-        Binary(Operator::Add, e1, e2) => interpret(e1) + interpret(e2),
-        Literal(l) => *l,
+        let exp = parse_expression("7-2*3", &ADD_FIRST).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(15));
+
+        let exp = parse_expression("100/10/5+17%5", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(4));
     }
-}
 
-fn main() {
-    let content = read_to_string("./inputs/day18.txt").expect("file not found");
+    #[test]
+    fn test_negation() {
+        let exp = parse_expression("-2^2", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(-4));
 
-    let lines = content.lines().map(remove_whitespace);
+        let exp = parse_expression("-2^2", &FLAT).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(4));
 
-    // Part 1
-    let result_1: i64 = lines
-        .clone()
-        .map(|l| parse_expression_part_1(&l))
-        .map(Result::unwrap)
-        .map(|x| interpret(&x))
-        .sum();
+        let exp = parse_expression("3*-(1+1)--1", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(-5));
+    }
 
-    // Result: 98621258158412
-    println!("{:?}", result_1);
+    #[test]
+    fn test_evaluation_errors() {
+        let exp = parse_expression("1/(2-2)", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Err(EvalError::DivisionByZero));
 
-    // Part 2
-    let result_2: i64 = lines
-        .clone()
-        .map(|l| parse_expression_part_2(&l))
-        .map(Result::unwrap)
-        .map(|e| interpret(&e))
-        .sum();
+        let exp = parse_expression("1%0", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Err(EvalError::DivisionByZero));
 
-    // Result: 241216538527890
-    println!("{:?}", result_2);
+        let exp = parse_expression("2^-1", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Err(EvalError::NegativeExponent));
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("12 +(3)"),
+            Ok(vec![
+                Token {
+                    kind: TokenKind::Number(BigInt::from(12)),
+                    span: 0..2
+                },
+                Token {
+                    kind: TokenKind::Operator(Operator::Add),
+                    span: 3..4
+                },
+                Token {
+                    kind: TokenKind::LeftParen,
+                    span: 4..5
+                },
+                Token {
+                    kind: TokenKind::Number(BigInt::from(3)),
+                    span: 5..6
+                },
+                Token {
+                    kind: TokenKind::RightParen,
+                    span: 6..7
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let source = "1 + (2 * 3";
+        let error = parse_expression(source, &STANDARD).unwrap_err();
+        assert_eq!(error.span, 10..10);
+        assert_eq!(
+            error.render(source),
+            "1 + (2 * 3\n          ^ missing closing )"
+        );
+
+        let source = "1 + 2 3";
+        let error = parse_expression(source, &STANDARD).unwrap_err();
+        assert_eq!(
+            error.render(source),
+            "1 + 2 3\n      ^ expected operator or end of input"
+        );
+
+        let source = "1 + 2)";
+        let error = parse_expression(source, &STANDARD).unwrap_err();
+        assert_eq!(error.render(source), "1 + 2)\n     ^ unmatched )");
+
+        let source = "1 + #";
+        let error = parse_expression(source, &STANDARD).unwrap_err();
+        assert_eq!(
+            error.render(source),
+            "1 + #\n    ^ unexpected character '#'"
+        );
+
+        let source = "1 * * 2";
+        let error = parse_expression(source, &STANDARD).unwrap_err();
+        assert_eq!(
+            error.render(source),
+            "1 * * 2\n    ^ expected number, - or ("
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let exp = parse_expression("1+(2^62*2)*1", &STANDARD).expect("correct parsing");
+        assert_eq!(
+            evaluate::<i64>(&exp),
+            Err(EvalError::Overflow(Binary(
+                Operator::Mul,
+                Rc::new(Binary(
+                    Operator::Pow,
+                    Rc::new(Literal(BigInt::from(2))),
+                    Rc::new(Literal(BigInt::from(62)))
+                )),
+                Rc::new(Literal(BigInt::from(2)))
+            )))
+        );
+        assert_eq!(evaluate::<i128>(&exp), Ok(2_i128.pow(63) + 1));
+        assert_eq!(evaluate::<BigInt>(&exp), Ok(BigInt::from(2).pow(63) + 1));
+
+        let exp = parse_expression("2^200-2^200+1", &STANDARD).expect("correct parsing");
+        assert!(evaluate::<i128>(&exp).is_err());
+        assert_eq!(evaluate::<BigInt>(&exp), Ok(BigInt::from(1)));
+
+        let literal: BigInt = "99999999999999999999".parse().unwrap();
+        let exp = parse_expression("99999999999999999999 + 1", &STANDARD).expect("correct parsing");
+        assert_eq!(
+            evaluate::<i64>(&exp),
+            Err(EvalError::Overflow(Literal(literal.clone())))
+        );
+        assert_eq!(evaluate::<i128>(&exp), Ok(100000000000000000000));
+        assert_eq!(evaluate::<BigInt>(&exp), Ok(literal + 1));
+        assert_eq!(run_code::<i64>(&compile(&exp)), Err(VmError::Overflow(0)));
+    }
+
+    #[test]
+    fn test_eval_error_display() {
+        let exp = parse_expression("1 + 2 ^ 62 * 2 * 3", &ADD_FIRST).expect("correct parsing");
+        let error = evaluate::<i64>(&exp).unwrap_err();
+        assert_eq!(error.to_string(), "overflow in (1 + 2 ^ 62) * 2");
+        assert_eq!(
+            error.display(&ADD_FIRST).to_string(),
+            "overflow in 1 + 2 ^ 62 * 2"
+        );
+        assert_eq!(EvalError::DivisionByZero.to_string(), "division by zero");
+    }
+
+    #[test]
+    fn test_huge_exponents() {
+        for (source, expected) in [
+            ("0^4294967296", 0),
+            ("1^4294967296", 1),
+            ("(-1)^4294967296", 1),
+            ("(-1)^4294967297", -1),
+            ("(-1)^0", 1),
+            ("0^0", 1),
+        ] {
+            let exp = parse_expression(source, &STANDARD).expect("correct parsing");
+            assert_eq!(evaluate::<i64>(&exp), Ok(expected), "{}", source);
+            assert_eq!(evaluate::<i128>(&exp), Ok(expected as i128), "{}", source);
+            assert_eq!(
+                evaluate::<BigInt>(&exp),
+                Ok(BigInt::from(expected)),
+                "{}",
+                source
+            );
+        }
+
+        for source in ["2^4294967296", "2^4000000000", "(2^1000000)^2", "2^1048576"] {
+            let exp = parse_expression(source, &STANDARD).expect("correct parsing");
+            assert!(
+                matches!(evaluate::<BigInt>(&exp), Err(EvalError::Overflow(_))),
+                "{}",
+                source
+            );
+        }
+        let exp = parse_expression("2^1048575", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<BigInt>(&exp).map(|n| n.bits()), Ok(1048576));
+    }
+
+    #[test]
+    fn test_number_types_agree() {
+        for source in ["-7/2", "-7%2", "3^4-(2*-5)", "((2+4*9)*(6+9*8+6)+6)+2+4*2"] {
+            let exp = parse_expression(source, &STANDARD).expect("correct parsing");
+            let expected = evaluate::<i64>(&exp).unwrap();
+            assert_eq!(evaluate::<i128>(&exp), Ok(expected as i128));
+            assert_eq!(evaluate::<BigInt>(&exp), Ok(BigInt::from(expected)));
+        }
+    }
+
+    fn ungrouped(expr: &Expression) -> Expression {
+        match expr {
+            Binary(op, left, right) => Binary(
+                op.clone(),
+                Rc::new(ungrouped(left)),
+                Rc::new(ungrouped(right)),
+            ),
+            Negate(inner) => Negate(Rc::new(ungrouped(inner))),
+            Literal(n) => Literal(n.clone()),
+            Variable(name) => Variable(name.clone()),
+            Grouped(inner) => ungrouped(inner),
+        }
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        let exp = parse_expression("((2 + 3)) * ((4))", &STANDARD).expect("correct parsing");
+        assert_eq!(pretty_print(&exp, &STANDARD), "(2 + 3) * 4");
+        assert_eq!(pretty_print(&exp, &FLAT), "2 + 3 * 4");
+
+        let exp =
+            parse_expression("(2^3)^2 - (1 - 1) - -(-2)^2", &STANDARD).expect("correct parsing");
+        assert_eq!(
+            pretty_print(&exp, &STANDARD),
+            "(2 ^ 3) ^ 2 - (1 - 1) - -(-2) ^ 2"
+        );
+
+        let sources = [
+            "1 + 2 * 3 + 4 * 5 + 6",
+            "2 * 3 + (4 * 5)",
+            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2",
+            "2 ^ 3 ^ 2 - -4 % 3 / (1 - 2 - 3)",
+            "--2 ^ -(3 * 4) - (5 - 6)",
+        ];
+        for table in [&FLAT, &ADD_FIRST, &STANDARD] {
+            for source in sources {
+                let exp = parse_expression(source, table).expect("correct parsing");
+                let printed = pretty_print(&exp, table);
+                let reparsed = parse_expression(&printed, table).expect("correct parsing");
+                assert_eq!(ungrouped(&reparsed), ungrouped(&exp), "{}", printed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewrite() {
+        assert_eq!(
+            rewrite("1 + 2 * 3 + 4 * 5 + 6", &FLAT, &STANDARD),
+            Ok("((1 + 2) * 3 + 4) * 5 + 6".to_owned())
+        );
+        assert_eq!(
+            rewrite("2 * 3 + (4 * 5)", &ADD_FIRST, &STANDARD),
+            Ok("2 * (3 + 4 * 5)".to_owned())
+        );
+        assert_eq!(
+            rewrite("2 * 3 + 4", &STANDARD, &ADD_FIRST),
+            Ok("(2 * 3) + 4".to_owned())
+        );
+
+        let source = "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2";
+        for table in [&FLAT, &ADD_FIRST] {
+            let rewritten = rewrite(source, table, &STANDARD).expect("correct parsing");
+            assert_eq!(
+                evaluate::<i64>(&parse_expression(&rewritten, &STANDARD).unwrap()),
+                evaluate::<i64>(&parse_expression(source, table).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let exp = parse_expression_part_2("2 * (3 + -4)").expect("correct parsing");
+        let code = compile(&exp);
+        assert_eq!(
+            code,
+            vec![
+                Instruction::Push(BigInt::from(2)),
+                Instruction::Push(BigInt::from(3)),
+                Instruction::Push(BigInt::from(4)),
+                Instruction::Negate,
+                Instruction::Binary(Operator::Add),
+                Instruction::Binary(Operator::Mul),
+            ]
+        );
+        assert_eq!(
+            disassemble(&code),
+            "0000 push 2\n0001 push 3\n0002 push 4\n0003 neg\n0004 add\n0005 mul\n"
+        );
+        assert_eq!(run_code::<i64>(&code), Ok(-2));
+    }
+
+    #[test]
+    fn test_vm_matches_evaluate() {
+        let sources = [
+            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2",
+            "2 ^ 3 ^ 2 - -4 % 3 / (1 - 2 - 3)",
+            "--2 ^ -(3 * 4) - (5 - 6)",
+            "-7 / 2 % 3",
+        ];
+        for table in [&FLAT, &ADD_FIRST, &STANDARD] {
+            for source in sources {
+                let exp = parse_expression(source, table).expect("correct parsing");
+                assert_eq!(
+                    run_code::<i64>(&compile(&exp)).ok(),
+                    evaluate::<i64>(&exp).ok()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_vm_errors() {
+        let run = |source| run_code::<i64>(&compile(&parse_expression(source, &STANDARD).unwrap()));
+        assert_eq!(run("1 + 1 / (2 - 2)"), Err(VmError::DivisionByZero));
+        assert_eq!(run("2 ^ -1"), Err(VmError::NegativeExponent));
+        assert_eq!(run("1 + 2 ^ 63"), Err(VmError::Overflow(3)));
+        assert_eq!(
+            run_code::<i64>(&[Instruction::Negate]),
+            Err(VmError::MalformedCode)
+        );
+        assert_eq!(
+            run_code::<i64>(&[
+                Instruction::Push(BigInt::from(1)),
+                Instruction::Push(BigInt::from(2))
+            ]),
+            Err(VmError::MalformedCode)
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let exp = parse_expression("x * -$12 + _y2", &STANDARD).expect("correct parsing");
+        let variables: Variables<i64> = [("x", 2), ("$12", 3), ("_y2", 10)]
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect();
+        assert_eq!(evaluate_in(&exp, &variables), Ok(4));
+        assert_eq!(run_code_in(&compile(&exp), &variables), Ok(4));
+        assert_eq!(
+            evaluate::<i64>(&exp),
+            Err(EvalError::UnboundVariable("x".to_owned()))
+        );
+        assert_eq!(
+            run_code::<i64>(&compile(&exp)),
+            Err(VmError::UnboundVariable("x".to_owned()))
+        );
+        assert_eq!(pretty_print(&exp, &STANDARD), "x * -$12 + _y2");
+
+        assert_eq!(
+            parse_expression("1 + $", &STANDARD),
+            Err(ParseError::new("expected result number after $", 4..5))
+        );
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.handle("1 + 2 * 3"), "$1 = 7");
+        assert_eq!(repl.handle(":mode add-first"), "mode add-first");
+        assert_eq!(repl.handle("let x = 1 + 2 * 3"), "$2 = x = 9");
+        assert_eq!(repl.handle(":mode flat"), "mode flat");
+        assert_eq!(repl.handle("$1 + x * 2"), "$3 = 32");
+        assert_eq!(
+            repl.handle("2 ^ 100"),
+            "$4 = 1267650600228229401496703205376"
+        );
+        assert_eq!(repl.handle("y + 1"), "unknown variable y");
+        assert_eq!(repl.handle("1 + 2 % (1 - 1)"), "division by zero");
+        assert_eq!(repl.handle("2 ^ -1"), "negative exponent");
+        // Flat mode: the overflowing part is shown as it'd be typed there.
+        assert_eq!(
+            repl.handle("1 + 2 ^ 1048575 * 2"),
+            "overflow in 1 + 2 ^ 1048575 * 2"
+        );
+        assert_eq!(repl.handle("let 2x = 1"), "invalid variable name \"2x\"");
+        assert_eq!(repl.handle("1 +"), "1 +\n   ^ expected number, - or (");
+        assert_eq!(
+            repl.handle(":mode fancy"),
+            "unknown mode \"fancy\", try flat, add-first or standard"
+        );
+        assert_eq!(repl.handle(":mode standard"), "mode standard");
+        assert_eq!(repl.handle("$4 / 2 ^ 99"), "$5 = 2");
+    }
+
+    #[test]
+    fn test_standard_precedence() {
+        let exp = parse_expression("2+3*4", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(14));
+
+        let exp = parse_expression("2*3+4*(1+1)", &STANDARD).expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(14));
+    }
+
+    #[test]
+    fn examples() {
+        let exp = parse_expression_part_2("1+(2*3)+(4*(5+6))").expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(51));
+
+        let exp = parse_expression_part_2("2*3+(4*5)").expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(46));
+
+        let exp = parse_expression_part_2("((2+4*9)*(6+9*8+6)+6)+2+4*2").expect("correct parsing");
+        assert_eq!(evaluate::<i64>(&exp), Ok(23340));
+    }
 }
@@ -1,126 +1,186 @@
-use std::boxed::Box;
-use std::collections::HashMap;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum Rule {
-    Or(Box<Rule>, Box<Rule>),
-    List(Vec<usize>),
-    Char(char),
-    /// Corresponds to the regex a+b.
-    Multiple(usize, usize),
+    /// Each alternative is a sequence of rule numbers, e.g. `1 2 | 3`.
+    Alt(Vec<Vec<usize>>),
+    Literal(String),
 }
 
 type Rules = HashMap<usize, Rule>;
 
-fn parse_number_list(list: &str) -> Vec<usize> {
-    list.split(' ')
-        .map(str::parse)
-        .map(Result::unwrap)
-        .collect()
+fn parse_number_list(list: &str) -> Result<Vec<usize>, String> {
+    let numbers = list
+        .split_whitespace()
+        .map(|n| {
+            n.parse()
+                .map_err(|_| format!("invalid rule number {:?}", n))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if numbers.is_empty() {
+        Err("empty alternative".to_owned())
+    } else {
+        Ok(numbers)
+    }
 }
 
-fn parse_rule(rule: &str) -> Rule {
-    if rule.chars().nth(0) == Some('"') {
-        Rule::Char(rule.chars().nth(1).unwrap())
-    } else if rule.contains('|') {
-        let mut rules = rule.split('|').map(str::trim);
-        Rule::Or(
-            Box::new(Rule::List(parse_number_list(rules.next().unwrap()))),
-            Box::new(Rule::List(parse_number_list(rules.next().unwrap()))),
-        )
+fn parse_rule(rule: &str) -> Result<Rule, String> {
+    if let Some(literal) = rule.strip_prefix('"') {
+        match literal.strip_suffix('"') {
+            Some(literal) if !literal.is_empty() && !literal.contains('"') => {
+                Ok(Rule::Literal(literal.to_owned()))
+            }
+            _ => Err(format!("malformed literal {}", rule)),
+        }
     } else {
-        Rule::List(parse_number_list(rule))
+        rule.split('|')
+            .map(parse_number_list)
+            .collect::<Result<_, _>>()
+            .map(Rule::Alt)
     }
 }
 
-fn parse_rule_line(rule_line: &str) -> (usize, Rule) {
-    let mut parts = rule_line.split(':');
-    let number: usize = parts.next().unwrap().parse().unwrap();
-    let rule = parse_rule(&parts.next().unwrap().trim());
+fn parse_rule_line(rule_line: &str) -> Result<(usize, Rule), String> {
+    let (number, rule) = rule_line
+        .split_once(':')
+        .ok_or_else(|| format!("missing ':' in {:?}", rule_line))?;
+    let number = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid rule number {:?}", number))?;
+    let rule = parse_rule(rule.trim()).map_err(|e| format!("rule {}: {}", number, e))?;
 
-    (number, rule)
+    Ok((number, rule))
 }
 
-fn parse_rules(rules: &str) -> Rules {
-    rules.lines().map(parse_rule_line).collect()
+fn parse_rules(rules: &str) -> Result<Rules, String> {
+    let mut result = Rules::new();
+
+    for (line_number, line) in rules.lines().enumerate() {
+        let (number, rule) =
+            parse_rule_line(line).map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+        if result.insert(number, rule).is_some() {
+            return Err(format!(
+                "line {}: rule {} defined twice",
+                line_number + 1,
+                number
+            ));
+        }
+    }
+
+    for (number, rule) in &result {
+        if let Rule::Alt(alternatives) = rule {
+            if let Some(missing) = alternatives
+                .iter()
+                .flatten()
+                .find(|r| !result.contains_key(r))
+            {
+                return Err(format!(
+                    "rule {} refers to undefined rule {}",
+                    number, missing
+                ));
+            }
+        }
+    }
+
+    Ok(result)
 }
 
 fn parse_messages(messages: &str) -> Vec<&str> {
     messages.lines().collect()
 }
-// TODO: Each match should return a substring since a match consumes some part of the string.
 
-fn matches_rule<'a>(rule: &Rule, rules: &Rules, message: &'a str) -> Option<&'a str> {
-    match rule {
-        Rule::Char(c) => {
-            if message.is_empty() {
-                return None;
-            }
-            let (left, right) = message.split_at(1);
-            if left == c.to_string() {
-                Some(right)
-            } else {
-                None
-            }
-        }
-        Rule::Or(left, right) => {
-            matches_rule(left, rules, message).or(matches_rule(right, rules, message))
-        }
-        Rule::List(list) => {
-            let mut rest = message;
-            for rule_index in list {
-                rest = matches_rule(rules.get(rule_index)?, rules, rest)?;
-            }
-            Some(rest)
-        }
-        Rule::Multiple(min_one_offset, delimiter) => {
-            //let first = matches_rule(rules.get(min_one_offset)?, rules, message)?;
-            let mut rest = matches_rule(rules.get(min_one_offset)?, rules, message)?;
-            loop {
-                let del = matches_rule(rules.get(delimiter)?, rules, rest);
-                if del.is_some() {
-                    return del;
-                }
-
-                rest = matches_rule(rules.get(min_one_offset)?, rules, rest)?;
-                //matches_rule(rules.get(min_one_offset)?, rules, rest);
-            }
-        }
+fn matches_sequence<'a>(sequence: &[usize], rules: &Rules, message: &'a str) -> HashSet<&'a str> {
+    let mut rests = HashSet::from([message]);
+    for rule_index in sequence {
+        let rule = match rules.get(rule_index) {
+            Some(rule) => rule,
+            None => return HashSet::new(),
+        };
+        rests = rests
+            .into_iter()
+            .flat_map(|rest| matches_rule(rule, rules, rest))
+            .collect();
     }
+    rests
 }
 
-fn get_terminal_characters(rules: &Rules, rule: &Rule) -> Vec<char> {
-    //let rule = rules.get(&rule).unwrap();
-
+/// Every remainder of `message` left over after a prefix matching `rule`. Trying all
+/// alternatives instead of committing to the first one makes recursive rules like `8: 42 | 42 8`
+/// work, as long as they aren't left-recursive.
+fn matches_rule<'a>(rule: &Rule, rules: &Rules, message: &'a str) -> HashSet<&'a str> {
     match rule {
-        Rule::Char(c) => vec![*c],
-        Rule::List(list) => list
+        Rule::Literal(literal) => message.strip_prefix(literal.as_str()).into_iter().collect(),
+        Rule::Alt(alternatives) => alternatives
             .iter()
-            .flat_map(|r| get_terminal_characters(rules, rules.get(r).unwrap()))
+            .flat_map(|sequence| matches_sequence(sequence, rules, message))
             .collect(),
-        Rule::Multiple(a, b) => {
-            let mut result = get_terminal_characters(rules, rules.get(a).unwrap());
-            let mut second = get_terminal_characters(rules, rules.get(b).unwrap());
-            result.append(&mut second);
-            result
-        }
-        Rule::Or(r1, r2) => {
-            let mut result = get_terminal_characters(rules, r1);
-            let mut second = get_terminal_characters(rules, r2);
-            result.append(&mut second);
-            result
-        }
     }
 }
 
 fn matches_rule_0(rules: &Rules, message: &str) -> bool {
     let rule_0 = rules.get(&0).expect("rule 0 should exist");
-    matches_rule(&rule_0, &rules, &message)
-        .filter(|str| str.is_empty())
-        .is_some()
+    matches_rule(rule_0, rules, message).contains("")
+}
+
+/// The regex pattern for rule `number`, or `None` if it can't match anything within the unrolling
+/// depth. `stack` holds the rules currently being expanded.
+fn rule_pattern(
+    rules: &Rules,
+    number: usize,
+    unroll: Option<usize>,
+    stack: &mut Vec<usize>,
+) -> Result<Option<String>, String> {
+    let nesting = stack.iter().filter(|r| **r == number).count();
+    match unroll {
+        _ if nesting == 0 => {}
+        None => return Err(format!("rule {} is recursive", number)),
+        Some(depth) if nesting > depth => return Ok(None),
+        Some(_) => {}
+    }
+
+    let alternatives = match rules.get(&number) {
+        Some(Rule::Literal(literal)) => return Ok(Some(regex::escape(literal))),
+        Some(Rule::Alt(alternatives)) => alternatives,
+        None => return Err(format!("undefined rule {}", number)),
+    };
+
+    stack.push(number);
+    let mut patterns = vec![];
+    'alternatives: for sequence in alternatives {
+        let mut pattern = String::new();
+        for rule in sequence {
+            match rule_pattern(rules, *rule, unroll, stack)? {
+                Some(p) => pattern.push_str(&p),
+                None => continue 'alternatives,
+            }
+        }
+        patterns.push(pattern);
+    }
+    stack.pop();
+
+    Ok(match patterns.len() {
+        0 => None,
+        1 => patterns.pop(),
+        _ => Some(format!("(?:{})", patterns.join("|"))),
+    })
+}
+
+/// Compiles rule `number` into an anchored regex. Recursive rules like 8 and 11 need an `unroll`
+/// depth: a rule may then contain itself at most that many times, deeper matches are missed.
+fn compile_rule(rules: &Rules, number: usize, unroll: Option<usize>) -> Result<Regex, String> {
+    let pattern = rule_pattern(rules, number, unroll, &mut vec![])?
+        .ok_or_else(|| format!("rule {} can't match within the unrolling depth", number))?;
+    Regex::new(&format!("^{}$", pattern)).map_err(|e| e.to_string())
 }
 
+// Rules 42 and 31 match 8 characters each and no message is longer than 96 characters, so rule 8
+// can't repeat 42 more than 10 times, i.e. it's nested in itself at most 9 times. Rule 11 needs less.
+const UNROLL: usize = 9;
+
 fn main() {
     let content = read_to_string("./inputs/day19.txt").expect("file not found");
 
@@ -129,55 +189,193 @@ fn main() {
     let rules = parts.next().unwrap();
     let messages = parts.next().unwrap();
 
-    let mut rules = parse_rules(&rules);
-    let messages = parse_messages(&messages);
+    let mut rules = parse_rules(rules).unwrap_or_else(|e| panic!("{}", e));
+    let messages = parse_messages(messages);
 
     for m in messages.clone() {
         println!("{}: {}", m, matches_rule_0(&rules, m));
     }
     // Result: 162
-    println!(
-        "{}",
-        messages
-            .iter()
-            .filter(|m| matches_rule_0(&rules, m))
-            .count()
-    );
-
-    // Try to match as many 42 as possible?
-    /*
-    rules.insert(
-        8,
-        Rule::Or(
-            Box::new(Rule::List(vec![42])),
-            Box::new(Rule::List(vec![42, 8])),
-        ),
-    );*/
-    //rules.insert(8, Rule::Multiple(42, 8));
-    //rules.insert(11, Rule::Multiple(42, 31));
-    /*
-    rules.insert(
-        11,
-        Rule::Or(
-            Box::new(Rule::List(vec![42, 31])),
-            Box::new(Rule::List(vec![42, 11, 31])),
-        ),
-    );*/
-    /*
-    println!(
-        "{:?}",
-        get_terminal_characters(&rules, rules.get(&42).unwrap())
-    );
-    println!(
-        "{:?}",
-        get_terminal_characters(&rules, rules.get(&31).unwrap())
-    );*/
-    println!(
-        "{}",
-        messages
+    let count = messages
+        .iter()
+        .filter(|m| matches_rule_0(&rules, m))
+        .count();
+    println!("{}", count);
+
+    let regex = compile_rule(&rules, 0, None).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(messages.iter().filter(|m| regex.is_match(m)).count(), count);
+
+    rules.insert(8, Rule::Alt(vec![vec![42], vec![42, 8]]));
+    rules.insert(11, Rule::Alt(vec![vec![42, 31], vec![42, 11, 31]]));
+    // Result: 267
+    let count = messages
+        .iter()
+        .filter(|m| matches_rule_0(&rules, m))
+        .count();
+    println!("{}", count);
+
+    let regex = compile_rule(&rules, 0, Some(UNROLL)).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(messages.iter().filter(|m| regex.is_match(m)).count(), count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_RULES: &str = r#"42: 9 14 | 10 1
+9: 14 27 | 1 26
+10: 23 14 | 28 1
+1: "a"
+11: 42 31
+5: 1 14 | 15 1
+19: 14 1 | 14 14
+12: 24 14 | 19 1
+16: 15 1 | 14 14
+31: 14 17 | 1 13
+6: 14 14 | 1 14
+2: 1 24 | 14 4
+0: 8 11
+13: 14 3 | 1 12
+15: 1 | 14
+17: 14 2 | 1 7
+23: 25 1 | 22 14
+28: 16 1
+4: 1 1
+20: 14 14 | 1 15
+3: 5 14 | 16 1
+27: 1 6 | 14 18
+14: "b"
+21: 14 1 | 1 14
+25: 1 1 | 1 14
+22: 14 14
+8: 42
+26: 14 22 | 1 20
+18: 15 15
+7: 14 5 | 1 21
+24: 14 1"#;
+
+    const EXAMPLE_MESSAGES: &str = "abbbbbabbbaaaababbaabbbbabababbbabbbbbbabaaaa
+bbabbbbaabaabba
+babbbbaabbbbbabbbbbbaabaaabaaa
+aaabbbbbbaaaabaababaabababbabaaabbababababaaa
+bbbbbbbaaaabbbbaaabbabaaa
+bbbababbbbaaaaaaaabbababaaababaabab
+ababaaaaaabaaab
+ababaaaaabbbaba
+baabbaaaabbaaaababbaababb
+abbbbabbbbaaaababbbbbbaaaababb
+aaaaabbaabaaaaababaa
+aaaabbaaaabbaaa
+aaaabbaabbaaaaaaabbbabbbaaabbaabaaa
+babaaabbbaaabaababbaabababaaab
+aabbbbbaabbbaaaaaabbbbbababaaaaabbaaabba";
+
+    fn count_matches(rules: &Rules) -> usize {
+        parse_messages(EXAMPLE_MESSAGES)
             .iter()
-            .filter(|m| matches_rule_0(&rules, m))
+            .filter(|m| matches_rule_0(rules, m))
             .count()
-    );
-    //println!("{:?}", messages);
+    }
+
+    #[test]
+    fn test_matches_rule_returns_all_remainders() {
+        let rules = parse_rules("0: 1 | 1 1\n1: \"a\"").unwrap();
+        assert_eq!(
+            matches_rule(rules.get(&0).unwrap(), &rules, "aab"),
+            HashSet::from(["ab", "b"])
+        );
+    }
+
+    #[test]
+    fn test_recursive_rules() {
+        let mut rules = parse_rules(EXAMPLE_RULES).unwrap();
+        assert_eq!(count_matches(&rules), 3);
+
+        rules.insert(8, Rule::Alt(vec![vec![42], vec![42, 8]]));
+        rules.insert(11, Rule::Alt(vec![vec![42, 31], vec![42, 11, 31]]));
+        assert_eq!(count_matches(&rules), 12);
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            parse_rule("1 2 | 3 | 4 5 6"),
+            Ok(Rule::Alt(vec![vec![1, 2], vec![3], vec![4, 5, 6]]))
+        );
+        assert_eq!(parse_rule("\"ab\""), Ok(Rule::Literal("ab".to_owned())));
+
+        let rules = parse_rules("0: 1 2 | 2 | 1 1 1\n1: \"ab\"\n2: \"c\"").unwrap();
+        for (message, expected) in [
+            ("abc", true),
+            ("c", true),
+            ("ababab", true),
+            ("abab", false),
+        ] {
+            assert_eq!(matches_rule_0(&rules, message), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_rule("\"\""), Err("malformed literal \"\"".to_owned()));
+        assert_eq!(parse_rule("\"a"), Err("malformed literal \"a".to_owned()));
+        assert_eq!(parse_rule("1 | | 2"), Err("empty alternative".to_owned()));
+        assert_eq!(
+            parse_rule("1 x"),
+            Err("invalid rule number \"x\"".to_owned())
+        );
+
+        assert_eq!(
+            parse_rules("0: 1\n1 \"a\""),
+            Err(r#"line 2: missing ':' in "1 \"a\"""#.to_owned())
+        );
+        assert_eq!(
+            parse_rules("0: 1\n1: 1 |"),
+            Err("line 2: rule 1: empty alternative".to_owned())
+        );
+        assert_eq!(
+            parse_rules("0: \"a\"\n0: \"b\""),
+            Err("line 2: rule 0 defined twice".to_owned())
+        );
+        assert_eq!(
+            parse_rules("0: 1 2\n1: \"a\""),
+            Err("rule 0 refers to undefined rule 2".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_compile_rule() {
+        let rules = parse_rules("0: 1 2 | 2\n1: \"a+\"\n2: \"b\"").unwrap();
+        let regex = compile_rule(&rules, 0, None).unwrap();
+        assert_eq!(regex.as_str(), r"^(?:a\+b|b)$");
+        assert!(regex.is_match("a+b"));
+        assert!(!regex.is_match("aab"));
+        assert!(!regex.is_match("bb"));
+
+        let mut rules = parse_rules(EXAMPLE_RULES).unwrap();
+        let regex = compile_rule(&rules, 0, None).unwrap();
+        for message in parse_messages(EXAMPLE_MESSAGES) {
+            assert_eq!(regex.is_match(message), matches_rule_0(&rules, message));
+        }
+
+        rules.insert(8, Rule::Alt(vec![vec![42], vec![42, 8]]));
+        rules.insert(11, Rule::Alt(vec![vec![42, 31], vec![42, 11, 31]]));
+        assert_eq!(
+            compile_rule(&rules, 0, None).unwrap_err(),
+            "rule 8 is recursive"
+        );
+        let regex = compile_rule(&rules, 0, Some(3)).unwrap();
+        for message in parse_messages(EXAMPLE_MESSAGES) {
+            assert_eq!(regex.is_match(message), matches_rule_0(&rules, message));
+        }
+        // Without any unrolling, 8 and 11 only match a single 42 and 42 31.
+        let regex = compile_rule(&rules, 0, Some(0)).unwrap();
+        assert_eq!(
+            parse_messages(EXAMPLE_MESSAGES)
+                .iter()
+                .filter(|m| regex.is_match(m))
+                .count(),
+            3
+        );
+    }
 }
@@ -1,8 +1,13 @@
 use itertools::Itertools;
 use itertools::MinMaxResult;
 use std::collections::HashSet;
+use std::env;
 use std::fs::read_to_string;
 
+#[path = "../life_rule.rs"]
+mod life_rule;
+use life_rule::Rule;
+
 #[derive(Debug)]
 enum Dir {
     E,
@@ -44,6 +49,9 @@ impl Dir {
     }
 }
 
+// The flipping rules from the puzzle.
+const PUZZLE_RULE: &str = "B2/S12";
+
 fn parse_line(line: &str) -> Vec<Dir> {
     let chars = line.chars().collect::<Vec<_>>();
 
@@ -70,14 +78,12 @@ fn parse_line(line: &str) -> Vec<Dir> {
     result
 }
 
-fn main() {
-    let input = read_to_string("./inputs/day24.txt").expect("file not found");
-    let dirs: Vec<Vec<Dir>> = input.lines().map(|l| parse_line(l)).collect();
-
-    // Using skewed coordinates, see https://www.redblobgames.com/grids/hexagons/#coordinates
+// Flips the tile at the end of each line. Uses skewed coordinates, see
+// https://www.redblobgames.com/grids/hexagons/#coordinates
+fn lay_tiles(input: &str) -> HashSet<(i32, i32)> {
     let mut floor: HashSet<(i32, i32)> = HashSet::new();
 
-    for row in dirs {
+    for row in input.lines().map(parse_line) {
         let tile = row.iter().fold((0, 0), |pos, dir| dir.walk(pos));
 
         if floor.contains(&tile) {
@@ -87,34 +93,112 @@ fn main() {
         }
     }
 
-    println!("Part 1: {}", floor.len());
+    floor
+}
 
-    for _ in 1..=100 {
+fn flip_days(mut floor: HashSet<(i32, i32)>, rule: &Rule, days: usize) -> HashSet<(i32, i32)> {
+    for _ in 1..=days {
         let mut new_floor = floor.clone();
 
-        let (min_x, max_x) = match floor.iter().map(|(x, _)| x).minmax() {
-            MinMaxResult::MinMax(a, b) => (*a, *b),
-            _ => panic!("fooo"),
+        // Without B0 nothing grows out of an empty floor.
+        let (min_x, max_x) = match floor.iter().map(|(x, _)| *x).minmax() {
+            MinMaxResult::MinMax(a, b) => (a, b),
+            MinMaxResult::OneElement(a) => (a, a),
+            MinMaxResult::NoElements => break,
         };
-        let (min_y, max_y) = match floor.iter().map(|(_, y)| y).minmax() {
-            MinMaxResult::MinMax(a, b) => (*a, *b),
-            _ => panic!("fooo"),
+        let (min_y, max_y) = match floor.iter().map(|(_, y)| *y).minmax() {
+            MinMaxResult::MinMax(a, b) => (a, b),
+            MinMaxResult::OneElement(a) => (a, a),
+            MinMaxResult::NoElements => break,
         };
 
         for (x, y) in (min_x - 2..max_x + 2).cartesian_product(min_y - 2..max_y + 2) {
             let adj_blacks: usize = Dir::neighbours((x, y)).intersection(&floor).count();
 
-            // black
-            if floor.contains(&(x, y)) && (adj_blacks == 0 || adj_blacks > 2) {
-                new_floor.remove(&(x, y));
-            // white
-            } else if adj_blacks == 2 {
+            let counts = if floor.contains(&(x, y)) {
+                &rule.survive
+            } else {
+                &rule.born
+            };
+            if counts.contains(&adj_blacks) {
                 new_floor.insert((x, y));
+            } else {
+                new_floor.remove(&(x, y));
             }
         }
 
         floor = new_floor;
     }
 
-    println!("Part 2: {}", floor.len());
+    floor
+}
+
+fn main() {
+    let input = read_to_string("./inputs/day24.txt").expect("file not found");
+    let floor = lay_tiles(&input);
+
+    // Result: 479
+    println!("Part 1: {}", floor.len());
+
+    // Usage: cargo run --bin day24 -- [--rule <B.../S...>]
+    let mut rule: Rule = PUZZLE_RULE.parse().unwrap();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rule" => {
+                let value = args.next().expect("--rule needs a value");
+                rule = value.parse().unwrap_or_else(|e| panic!("{}", e));
+            }
+            other => panic!("unknown argument {:?}", other),
+        }
+    }
+
+    // Result: 4135
+    println!("Part 2: {}", flip_days(floor, &rule, 100).len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "sesenwnenenewseeswwswswwnenewsewsw
+neeenesenwnwwswnenewnwwsewnenwseswesw
+seswneswswsenwwnwse
+nwnwneseeswswnenewneswwnewseswneseene
+swweswneswnenwsewnwneneseenw
+eesenwseswswnenwswnwnwsewwnwsene
+sewnenenenesenwsewnenwwwse
+wenwwweseeeweswwwnwwe
+wsweesenenewnwwnwsenewsenwwsesesenwne
+neeswseenwwswnwswswnw
+nenwswwsewswnenenewsenwsenwnesesenew
+enewnwewneswsewnwswenweswnenwsenwsw
+sweneswneswneneenwnewenewwneswswnese
+swwesenesewenwneswnwwneseswwne
+enesenwswwswneneswsenwnewswseenwsese
+wnwnesenesenenwwnenwsewesewsesesew
+nenewswnwewswnenesenwnesewesw
+eneswnwswnwsenenwnwnwwseeswneewsenese
+neswnwewnwnwseenwseesewsenwsweewe
+wseweeenwnesenwwwswnew";
+
+    #[test]
+    fn test_puzzle_rule() {
+        let floor = lay_tiles(EXAMPLE);
+        assert_eq!(floor.len(), 10);
+
+        let rule: Rule = PUZZLE_RULE.parse().unwrap();
+        assert_eq!(flip_days(floor.clone(), &rule, 1).len(), 15);
+        assert_eq!(flip_days(floor, &rule, 10).len(), 37);
+    }
+
+    #[test]
+    fn test_floor_dying_out() {
+        let rule: Rule = "B6/S6".parse().unwrap();
+        assert_eq!(flip_days(lay_tiles(EXAMPLE), &rule, 3).len(), 0);
+
+        // A single tile without neighbours survives with S0.
+        let rule: Rule = "B6/S0".parse().unwrap();
+        assert_eq!(flip_days(lay_tiles("e"), &rule, 3), HashSet::from([(1, 0)]));
+    }
 }
@@ -0,0 +1,97 @@
+// Shared by the day17 and day24 automata.
+
+use std::str::FromStr;
+
+/// A Life-like rule in B/S notation, e.g. `B3/S23`: dead cells with 3 live neighbours are born,
+/// live cells with 2 or 3 live neighbours survive. Counts above 9 can be given comma-separated,
+/// e.g. `B3/S2,3,10`.
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    pub born: Vec<usize>,
+    pub survive: Vec<usize>,
+}
+
+fn parse_counts(counts: &str) -> Result<Vec<usize>, String> {
+    if counts.contains(',') {
+        counts
+            .split(',')
+            .map(|c| c.parse().map_err(|_| format!("invalid count {:?}", c)))
+            .collect()
+    } else {
+        counts
+            .chars()
+            .map(|c| {
+                c.to_digit(10)
+                    .map(|d| d as usize)
+                    .ok_or_else(|| format!("invalid count {:?}", c))
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut born = None;
+        let mut survive = None;
+
+        for part in s.split('/') {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some('B') | Some('b') => born = Some(parse_counts(chars.as_str())?),
+                Some('S') | Some('s') => survive = Some(parse_counts(chars.as_str())?),
+                _ => return Err(format!("expected B or S part, got {:?}", part)),
+            }
+        }
+
+        let born = born.ok_or("missing B part")?;
+        // Every cell of the infinite world would be born at once.
+        if born.contains(&0) {
+            return Err("rules with B0 can't be simulated".to_owned());
+        }
+
+        Ok(Rule {
+            born,
+            survive: survive.ok_or("missing S part")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            "B36/S23".parse(),
+            Ok(Rule {
+                born: vec![3, 6],
+                survive: vec![2, 3]
+            })
+        );
+        assert_eq!(
+            "B2/S".parse(),
+            Ok(Rule {
+                born: vec![2],
+                survive: vec![]
+            })
+        );
+        assert_eq!(
+            "B3/S2,3,10".parse(),
+            Ok(Rule {
+                born: vec![3],
+                survive: vec![2, 3, 10]
+            })
+        );
+        assert!("B3".parse::<Rule>().is_err());
+        assert!("B3/X23".parse::<Rule>().is_err());
+        assert!("Bx/S23".parse::<Rule>().is_err());
+        assert_eq!(
+            "B03/S23".parse::<Rule>(),
+            Err("rules with B0 can't be simulated".to_owned())
+        );
+        assert!("B3,0/S23".parse::<Rule>().is_err());
+    }
+}